@@ -0,0 +1,189 @@
+use crate::damage::{DamageType, Resistances};
+use crate::entity::{EntityId, World};
+use crate::equipment::Slot;
+use crate::item::{from_dungeon_level, Transition};
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use tcod::colors::*;
+use tcod::Color;
+
+pub const AFFIXES_FILE: &str = "data/affixes.json";
+
+// Percent chance by dungeon level for equipment to be found with one affix, or with both a
+// prefix and a suffix
+const MAGIC_CHANCE: &[Transition] = &[
+    Transition {
+        level: 1,
+        value: 10,
+    },
+    Transition {
+        level: 3,
+        value: 20,
+    },
+    Transition {
+        level: 6,
+        value: 30,
+    },
+];
+const RARE_CHANCE: &[Transition] = &[
+    Transition { level: 3, value: 5 },
+    Transition {
+        level: 6,
+        value: 10,
+    },
+    Transition {
+        level: 9,
+        value: 20,
+    },
+];
+
+// How many affixes a piece of equipment rolled, which shows in its color
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Rarity {
+    #[default]
+    Common,
+    Magic,
+    Rare,
+}
+
+impl Rarity {
+    // Common items keep their own color
+    pub fn color(self) -> Option<Color> {
+        match self {
+            Rarity::Common => None,
+            Rarity::Magic => Some(LIGHT_BLUE),
+            Rarity::Rare => Some(YELLOW),
+        }
+    }
+}
+
+// Something extra a weapon does to whatever it hits
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum OnHit {
+    Damage {
+        damage: i32,
+        damage_type: DamageType,
+    },
+    // Heals the wielder by a percentage of the damage dealt
+    LifeDrain {
+        percent: i32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum AffixKind {
+    Prefix,
+    Suffix,
+}
+
+// A random modifier for equipment as described in the data file, like "Vampiric" or "of the Bear"
+#[derive(Debug, Deserialize)]
+pub struct AffixTemplate {
+    pub name: String,
+    pub kind: AffixKind,
+    // The slots it can appear on, or any slot if empty
+    #[serde(default)]
+    pub slots: Vec<Slot>,
+    #[serde(default)]
+    pub power_bonus: i32,
+    #[serde(default)]
+    pub defense_bonus: i32,
+    #[serde(default)]
+    pub max_hp_bonus: i32,
+    #[serde(default)]
+    pub resistances: Resistances,
+    pub on_hit: Option<OnHit>,
+    // Spawn weight by dungeon level
+    pub spawn: Vec<Transition>,
+}
+
+impl AffixTemplate {
+    fn fits(&self, slot: Slot) -> bool {
+        self.slots.is_empty() || self.slots.contains(&slot)
+    }
+}
+
+fn choose_affix(
+    kind: AffixKind,
+    slot: Slot,
+    level: u32,
+    affixes: &[AffixTemplate],
+) -> Option<&AffixTemplate> {
+    let weights = affixes
+        .iter()
+        .map(|affix| {
+            if affix.kind == kind && affix.fits(slot) {
+                from_dungeon_level(&affix.spawn, level)
+            } else {
+                0
+            }
+        })
+        .collect::<Vec<_>>();
+    let choice = WeightedIndex::new(&weights).ok()?;
+    Some(&affixes[choice.sample(&mut rand::thread_rng())])
+}
+
+// Maybe give a freshly generated piece of equipment a prefix, a suffix or both,
+// more often the deeper it is found
+pub fn roll_affixes(item_id: EntityId, level: u32, affixes: &[AffixTemplate], world: &mut World) {
+    let slot = match world.equipment.get(item_id) {
+        Some(equipment) => equipment.slot,
+        None => return,
+    };
+    let mut rng = rand::thread_rng();
+    let roll = rng.gen_range(0, 100);
+    let rare_chance = from_dungeon_level(RARE_CHANCE, level);
+    let magic_chance = from_dungeon_level(MAGIC_CHANCE, level);
+    let kinds = if roll < rare_chance {
+        vec![AffixKind::Prefix, AffixKind::Suffix]
+    } else if roll < rare_chance + magic_chance {
+        vec![*[AffixKind::Prefix, AffixKind::Suffix]
+            .choose(&mut rng)
+            .unwrap()]
+    } else {
+        return;
+    };
+
+    let mut prefix = None;
+    let mut suffix = None;
+    for kind in kinds {
+        let affix = match choose_affix(kind, slot, level, affixes) {
+            Some(affix) => affix,
+            None => continue,
+        };
+        let equipment = &mut world.equipment[item_id];
+        // A weapon only gets one on-hit effect
+        if affix.on_hit.is_some() && equipment.on_hit.is_some() {
+            continue;
+        }
+        equipment.power_bonus += affix.power_bonus;
+        equipment.defense_bonus += affix.defense_bonus;
+        equipment.max_hp_bonus += affix.max_hp_bonus;
+        equipment.resistances += affix.resistances;
+        if affix.on_hit.is_some() {
+            equipment.on_hit = affix.on_hit;
+        }
+        match kind {
+            AffixKind::Prefix => prefix = Some(&affix.name),
+            AffixKind::Suffix => suffix = Some(&affix.name),
+        }
+    }
+
+    let rarity = match (prefix, suffix) {
+        (Some(_), Some(_)) => Rarity::Rare,
+        (None, None) => return,
+        _ => Rarity::Magic,
+    };
+    let name = &mut world.names[item_id];
+    if let Some(prefix) = prefix {
+        *name = format!("{} {}", prefix, name);
+    }
+    if let Some(suffix) = suffix {
+        *name = format!("{} {}", name, suffix);
+    }
+    world.equipment[item_id].rarity = rarity;
+    if let Some(color) = rarity.color() {
+        world.renderables[item_id].color = color;
+    }
+}
@@ -1,11 +1,23 @@
-use crate::map::{Map, PLAYER};
-use crate::object::Object;
+use crate::entity::{EntityId, World, PLAYER};
+use crate::map::{line_of_sight, Map, TORCH_RADIUS};
+use crate::object::{free_spot_near, is_blocked};
 use crate::Tcod;
 use rand::Rng;
 use std::cmp;
-use tcod::colors::{DARK_RED, ORANGE, RED};
+use tcod::colors::{DARK_RED, LIGHT_GREY, LIGHT_VIOLET, ORANGE, RED, WHITE};
 
+use crate::damage::{DamageType, Resistances};
+use crate::encumbrance::CORPSE_WEIGHT_PER_HP;
 use crate::game::Game;
+use crate::hunger::CORPSE_NUTRITION_PER_HP;
+use crate::identify::item_name;
+use crate::item::{
+    add_to_inventory, confuse, equip_if_free, fireball_blast, lightning_strike, place_item,
+    take_one, Item,
+};
+use crate::ranged::trace_projectile;
+use crate::shop::{drop_gold, is_angry};
+use crate::status::StatusKind;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
@@ -15,42 +27,76 @@ pub enum DeathCallback {
 }
 
 impl DeathCallback {
-    pub fn callback(self, object: &mut Object, game: &mut Game) {
+    pub fn callback(self, id: EntityId, world: &mut World, game: &mut Game) {
         use DeathCallback::*;
         let callback = match self {
             Player => player_death,
             Monster => monster_death,
         };
-        callback(object, game);
+        callback(id, world, game);
     }
 }
 
-fn player_death(player: &mut Object, game: &mut Game) {
+fn player_death(player_id: EntityId, world: &mut World, game: &mut Game) {
     // Game over
     game.messages.add("You died!", RED);
 
     // Turn the player into a corpse
-    player.char = '%';
-    player.color = DARK_RED;
+    let renderable = &mut world.renderables[player_id];
+    renderable.glyph = '%';
+    renderable.color = DARK_RED;
 }
 
-fn monster_death(monster: &mut Object, game: &mut Game) {
-    game.messages.add(
-        format!(
-            "{} is dead! You gain {} experience points.",
-            monster.name,
-            monster.fighter.unwrap().xp,
-        ),
-        ORANGE,
-    );
+fn monster_death(monster_id: EntityId, world: &mut World, game: &mut Game) {
+    let name = world.names[monster_id].clone();
+    if world.factions.get(monster_id) == Some(&Faction::Player) {
+        game.messages
+            .add(format!("Your ally {} is dead!", name), RED);
+    } else {
+        game.messages.add(
+            format!(
+                "{} is dead! You gain {} experience points.",
+                name, world.fighters[monster_id].xp,
+            ),
+            ORANGE,
+        );
+    }
 
-    // Turn the monster into a corpse
-    monster.char = '%';
-    monster.color = DARK_RED;
-    monster.blocks = false;
-    monster.fighter = None;
-    monster.ai = None;
-    monster.name = format!("Remains of {}", monster.name);
+    // Turn the monster into a corpse, which can be eaten
+    let nutrition = world.max_hp(monster_id) * CORPSE_NUTRITION_PER_HP;
+    world.items.insert(monster_id, Item::Food { nutrition });
+    let weight = world.max_hp(monster_id) as f32 * CORPSE_WEIGHT_PER_HP;
+    world.weights.insert(monster_id, weight);
+    let renderable = &mut world.renderables[monster_id];
+    renderable.glyph = '%';
+    renderable.color = DARK_RED;
+    world.blockers.remove(monster_id);
+    world.fighters.remove(monster_id);
+    world.ais.remove(monster_id);
+    world.awareness.remove(monster_id);
+    // Everything it carried falls to the ground
+    if let Some(inventory) = world.inventories.remove(monster_id) {
+        let (x, y) = world.pos(monster_id);
+        for item_id in inventory {
+            if let Some(equipment) = world.equipment.get_mut(item_id) {
+                equipment.equipped = false;
+            }
+            place_item(item_id, x, y, world);
+        }
+    }
+    if let Some(purse) = world.purses.remove(monster_id) {
+        if purse.gold > 0 {
+            let (x, y) = world.pos(monster_id);
+            drop_gold(purse.gold, x, y, &game.templates, world);
+        }
+    }
+    if world.uniques.contains(monster_id) {
+        game.history.push(format!(
+            "{} was slain on dungeon level {}.",
+            name, game.dungeon_level
+        ));
+    }
+    world.names[monster_id] = format!("Remains of {}", name);
 }
 
 #[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
@@ -60,9 +106,117 @@ pub struct Fighter {
     pub base_defense: i32,
     pub base_power: i32,
     pub xp: i32,
+    // Only the player gains levels
+    pub level: i32,
+    pub resistances: Resistances,
     pub on_death: DeathCallback,
 }
 
+const ARCHER_RANGE: i32 = 6;
+// Archers try to back off when the player gets closer than this
+const ARCHER_MIN_DISTANCE: f32 = 3.0;
+
+// Number of turns a caster needs to recover after casting a spell
+const CASTER_COOLDOWN: i32 = 4;
+// Casters know the spells on these scrolls, and cast them just as strongly
+const CASTER_SCROLLS: [&str; 3] = [
+    "Scroll of Lightning Bolt",
+    "Scroll of Confusion",
+    "Scroll of Fireball",
+];
+
+// Monsters run away below this fraction of their max HP, and come back above the other
+const FLEE_HP_FRACTION: f32 = 0.25;
+const RETURN_HP_FRACTION: f32 = 0.75;
+// HP regained per turn while keeping away from the player
+const FLEE_REGEN: i32 = 1;
+// Monsters drink a healing potion when they're down to this fraction of their max HP
+const DRINK_HP_FRACTION: f32 = 0.5;
+// Distance within which monsters count as part of the same group
+const PACK_RADIUS: f32 = 6.0;
+
+const DEFAULT_VISION_RADIUS: i32 = 8;
+
+// How long a monster stays enraged after another kind of monster hurts it
+pub const RAGE_TURNS: i32 = 15;
+// Allies try to stay within this distance of the player
+const ALLY_FOLLOW_DISTANCE: f32 = 3.0;
+
+// Turns a monster keeps hunting after losing track of the player
+pub const HUNT_TURNS: i32 = 20;
+// Chance per turn to spot the player in torchlight, and beyond it
+const LIT_NOTICE_CHANCE: f32 = 0.8;
+const DARK_NOTICE_CHANCE: f32 = 0.2;
+
+// Which side a creature fights for
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Faction {
+    Player,
+    Greenskin,
+    Troll,
+    Beast,
+    Elemental,
+    // Shopkeepers, who keep to themselves unless robbed
+    Merchant,
+}
+
+// Whether an attack from one faction on another makes the victim fight back.
+// Fights involving the player's side happen anyway
+pub fn provokes(attacker: Option<Faction>, target: Option<Faction>) -> bool {
+    match (attacker, target) {
+        (Some(attacker), Some(target)) => {
+            attacker != target && attacker != Faction::Player && target != Faction::Player
+        }
+        _ => false,
+    }
+}
+
+impl World {
+    // The player's side and the monsters are always at war. Monsters of different
+    // kinds leave each other alone unless one of them is enraged
+    pub fn is_hostile(&self, id: EntityId, other: EntityId) -> bool {
+        match (self.factions.get(id), self.factions.get(other)) {
+            (Some(&faction), Some(&other_faction)) if faction != other_faction => {
+                // Merchants only fight whoever stole from them
+                if faction == Faction::Merchant {
+                    return other_faction == Faction::Player && is_angry(id, self);
+                }
+                if other_faction == Faction::Merchant {
+                    return faction == Faction::Player && is_angry(other, self);
+                }
+                faction == Faction::Player
+                    || other_faction == Faction::Player
+                    || self.has_status(id, StatusKind::Enraged)
+            }
+            _ => false,
+        }
+    }
+}
+
+// How far a creature can see. A radius of 0 means it's blind
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Vision {
+    pub radius: i32,
+    pub darkvision: bool,
+}
+
+impl Default for Vision {
+    fn default() -> Self {
+        Vision {
+            radius: DEFAULT_VISION_RADIUS,
+            darkvision: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Awareness {
+    Asleep,
+    Unaware,
+    // Heading for where the player was last seen or heard
+    Hunting { x: i32, y: i32, turns_left: i32 },
+}
+
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Ai {
     Basic,
@@ -70,48 +224,491 @@ pub enum Ai {
         previous_ai: Box<Ai>,
         num_turns: i32,
     },
+    Archer,
+    Caster {
+        cooldown: i32,
+    },
+    Pack,
+    Shopkeeper,
+    Fleeing {
+        previous_ai: Box<Ai>,
+    },
+    Ally,
 }
 
-pub fn move_towards(id: usize, target_x: i32, target_y: i32, map: &Map, objects: &mut [Object]) {
-    let dx = target_x - objects[id].x;
-    let dy = target_y - objects[id].y;
+// Special powers that work alongside a monster's AI
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Ability {
+    // Heals this many hit points every turn
+    Regeneration {
+        hp: i32,
+    },
+    // Heals by the damage it deals in melee
+    LifeDrain,
+    // Calls a monster of the named kind to its side, then rests for `cooldown` turns
+    Summon {
+        monster: String,
+        cooldown: i32,
+        #[serde(default)]
+        wait: i32,
+    },
+}
+
+pub fn move_towards(id: EntityId, target_x: i32, target_y: i32, map: &Map, world: &mut World) {
+    let (x, y) = world.pos(id);
+    let dx = target_x - x;
+    let dy = target_y - y;
     let distance = ((dx.pow(2) + dy.pow(2)) as f32).sqrt();
 
     let dx = (dx as f32 / distance).round() as i32;
     let dy = (dy as f32 / distance).round() as i32;
-    Object::move_by(id, dx, dy, map, objects);
+    world.move_by(id, dx, dy, map);
+}
+
+// Take the free step that best improves the distance to the target, going around
+// anything in the way. Returns false if no step gets closer (or further, if fleeing)
+fn step_relative_to(
+    id: EntityId,
+    target_x: i32,
+    target_y: i32,
+    away: bool,
+    map: &Map,
+    world: &mut World,
+) -> bool {
+    let (x, y) = world.pos(id);
+    let score = |x: i32, y: i32| {
+        let distance = (((x - target_x).pow(2) + (y - target_y).pow(2)) as f32).sqrt();
+        if away {
+            -distance
+        } else {
+            distance
+        }
+    };
+    let mut best = None;
+    let mut best_score = score(x, y);
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            if (dx, dy) != (0, 0) && !is_blocked(x + dx, y + dy, map, world) {
+                let step_score = score(x + dx, y + dy);
+                if step_score < best_score {
+                    best = Some((dx, dy));
+                    best_score = step_score;
+                }
+            }
+        }
+    }
+    match best {
+        Some((dx, dy)) => {
+            world.move_by(id, dx, dy, map);
+            true
+        }
+        None => false,
+    }
+}
+
+pub fn step_towards(
+    id: EntityId,
+    target_x: i32,
+    target_y: i32,
+    map: &Map,
+    world: &mut World,
+) -> bool {
+    step_relative_to(id, target_x, target_y, false, map, world)
+}
+
+pub fn move_away(id: EntityId, target_x: i32, target_y: i32, map: &Map, world: &mut World) -> bool {
+    step_relative_to(id, target_x, target_y, true, map, world)
+}
+
+// Whether the target is within the monster's own sight. Anything right next to
+// a monster is noticed by touch and smell, even if it's blind
+fn can_see(monster_id: EntityId, target_id: EntityId, map: &Map, world: &World) -> bool {
+    let distance = world.distance_to(monster_id, target_id);
+    if distance < 2.0 {
+        return true;
+    }
+    if world.has_status(target_id, StatusKind::Invisible) {
+        return false;
+    }
+    let vision = world.visions.get(monster_id).copied().unwrap_or_default();
+    distance <= vision.radius as f32
+        && line_of_sight(world.pos(monster_id), world.pos(target_id), map)
+}
+
+fn can_see_player(monster_id: EntityId, map: &Map, world: &World) -> bool {
+    can_see(monster_id, PLAYER, map, world)
+}
+
+// The closest creature the monster can see and wants to fight
+fn closest_enemy(monster_id: EntityId, map: &Map, world: &World) -> Option<EntityId> {
+    world
+        .fighters
+        .iter()
+        .map(|(id, _)| id)
+        .filter(|&id| {
+            id != monster_id
+                && world.is_alive(id)
+                && world.is_hostile(monster_id, id)
+                && can_see(monster_id, id, map, world)
+        })
+        .min_by(|&id1, &id2| {
+            world
+                .distance_to(monster_id, id1)
+                .partial_cmp(&world.distance_to(monster_id, id2))
+                .unwrap()
+        })
+}
+
+// Attack the enemy if it's next to the monster, otherwise go after it
+fn fight(monster_id: EntityId, enemy_id: EntityId, game: &mut Game, world: &mut World) {
+    if world.distance_to(monster_id, enemy_id) < 2.0 {
+        world.attack(monster_id, enemy_id, game);
+    } else {
+        let (enemy_x, enemy_y) = world.pos(enemy_id);
+        step_towards(monster_id, enemy_x, enemy_y, &game.map, world);
+    }
+}
+
+// Attack an enemy standing right next to the monster, if there is one
+fn attack_adjacent(monster_id: EntityId, game: &mut Game, world: &mut World) -> bool {
+    match closest_enemy(monster_id, &game.map, world) {
+        Some(enemy_id) if world.distance_to(monster_id, enemy_id) < 2.0 => {
+            fight(monster_id, enemy_id, game, world);
+            true
+        }
+        _ => false,
+    }
+}
+
+fn sees_player(monster_id: EntityId, map: &Map, world: &World) -> bool {
+    let awake = world.awareness.get(monster_id) != Some(&Awareness::Asleep);
+    awake && world.is_alive(PLAYER) && can_see_player(monster_id, map, world)
+}
+
+// Whether the player can see the monster, so its doings are worth reporting
+fn in_view(monster_id: EntityId, tcod: &Tcod, world: &World) -> bool {
+    let (x, y) = world.pos(monster_id);
+    tcod.fov.is_in_fov(x, y)
+}
+
+// Update what the monster knows about the player from what it sees and hears.
+// Returns true if the monster is hunting the player
+pub fn update_awareness(
+    monster_id: EntityId,
+    tcod: &Tcod,
+    game: &mut Game,
+    world: &mut World,
+) -> bool {
+    use Awareness::*;
+    let mut awareness = world.awareness.get(monster_id).copied().unwrap_or(Unaware);
+    // Only report what the player can actually see
+    let visible = in_view(monster_id, tcod, world);
+
+    // Loud noises wake monsters up and draw them in. Sleepers only hear half as far
+    for noise in game.noises.iter() {
+        let range = match awareness {
+            Asleep => noise.loudness as f32 / 2.0,
+            _ => noise.loudness as f32,
+        };
+        if world.distance(monster_id, noise.x, noise.y) > range {
+            continue;
+        }
+        if awareness == Asleep && visible {
+            game.messages.add(
+                format!("The {} wakes up!", world.names[monster_id]),
+                LIGHT_GREY,
+            );
+        }
+        awareness = Hunting {
+            x: noise.x,
+            y: noise.y,
+            turns_left: HUNT_TURNS,
+        };
+    }
+
+    world.awareness.insert(monster_id, awareness);
+    if sees_player(monster_id, &game.map, world) {
+        if awareness == Unaware {
+            // The player's torch makes them easy to spot up close, further away
+            // only monsters that see in the dark pick them out reliably
+            let distance = world.distance_to(monster_id, PLAYER);
+            let darkvision = world.visions.get(monster_id).is_some_and(|v| v.darkvision);
+            let chance = if distance <= TORCH_RADIUS as f32 || darkvision {
+                LIT_NOTICE_CHANCE
+            } else {
+                DARK_NOTICE_CHANCE
+            };
+            if rand::random::<f32>() >= chance {
+                return false;
+            }
+            if visible {
+                game.messages.add(
+                    format!("The {} notices you!", world.names[monster_id]),
+                    ORANGE,
+                );
+            }
+        }
+        let (player_x, player_y) = world.pos(PLAYER);
+        awareness = Hunting {
+            x: player_x,
+            y: player_y,
+            turns_left: HUNT_TURNS,
+        };
+    } else if let Hunting { x, y, turns_left } = awareness {
+        // Lost sight of the player, eventually give up the search
+        awareness = if turns_left > 0 {
+            Hunting {
+                x,
+                y,
+                turns_left: turns_left - 1,
+            }
+        } else {
+            Unaware
+        };
+    }
+    world.awareness.insert(monster_id, awareness);
+    matches!(awareness, Hunting { .. })
 }
 
-pub fn ai_take_turn(monster_id: usize, tcod: &Tcod, game: &mut Game, objects: &mut [Object]) {
+// Head for wherever the monster thinks the player is
+fn hunt(monster_id: EntityId, game: &Game, world: &mut World) {
+    if let Some(&Awareness::Hunting { x, y, .. }) = world.awareness.get(monster_id) {
+        if world.pos(monster_id) != (x, y) {
+            move_towards(monster_id, x, y, &game.map, world);
+        }
+    }
+}
+
+// Let every monster take its turn. Anything killed along the way is skipped
+pub fn run_ai(tcod: &Tcod, game: &mut Game, world: &mut World) {
+    for id in world.ais.ids() {
+        if world.ais.contains(id) {
+            ai_take_turn(id, tcod, game, world);
+        }
+    }
+}
+
+pub fn ai_take_turn(monster_id: EntityId, tcod: &Tcod, game: &mut Game, world: &mut World) {
     use Ai::*;
-    if let Some(ai) = objects[monster_id].ai.take() {
+    let visible = in_view(monster_id, tcod, world);
+    for kind in world.tick_statuses(monster_id) {
+        if visible {
+            game.messages.add(
+                format!("The {} is no longer {}!", world.names[monster_id], kind),
+                LIGHT_GREY,
+            );
+        }
+    }
+    world.suffer_poison(monster_id, game);
+    if !world.ais.contains(monster_id) {
+        // The poison finished it off
+        return;
+    }
+
+    // Allies always know where the player is
+    let ally = world.factions.get(monster_id) == Some(&Faction::Player);
+    // Shopkeepers pay the player no mind until they're robbed
+    let minding_shop = world.shopkeepers.contains(monster_id) && !is_angry(monster_id, world);
+    let hunting = ally || (!minding_shop && update_awareness(monster_id, tcod, game, world));
+    use_abilities(monster_id, tcod, game, world, hunting);
+    if drink_potion(monster_id, tcod, game, world) {
+        return;
+    }
+    let rage_target = if world.has_status(monster_id, StatusKind::Enraged) {
+        closest_enemy(monster_id, &game.map, world)
+    } else {
+        None
+    };
+    if let Some(ai) = world.ais.remove(monster_id) {
         let new_ai = match ai {
-            Basic => ai_basic(monster_id, tcod, game, objects),
             Confused {
                 previous_ai,
                 num_turns,
-            } => ai_confused(monster_id, tcod, game, objects, previous_ai, num_turns),
+            } => ai_confused(monster_id, tcod, game, world, previous_ai, num_turns),
+            Ally => ai_ally(monster_id, tcod, game, world),
+            Fleeing { previous_ai } => ai_fleeing(monster_id, tcod, game, world, previous_ai),
+            ai if morale_breaks(monster_id, world) => {
+                if in_view(monster_id, tcod, world) {
+                    game.messages.add(
+                        format!("The {} turns to flee!", world.names[monster_id]),
+                        LIGHT_GREY,
+                    );
+                }
+                ai_fleeing(monster_id, tcod, game, world, Box::new(ai))
+            }
+            // Frightened monsters only try to get away from the player
+            ai if world.has_status(monster_id, StatusKind::Afraid) => {
+                let (player_x, player_y) = world.pos(PLAYER);
+                move_away(monster_id, player_x, player_y, &game.map, world);
+                ai
+            }
+            // Enraged monsters turn on whatever enemy is closest
+            ai if rage_target.is_some() => {
+                fight(monster_id, rage_target.unwrap(), game, world);
+                ai
+            }
+            Shopkeeper => ai_shopkeeper(monster_id, tcod, game, world, hunting),
+            // Monsters that are asleep or haven't noticed anything stay put
+            ai if !hunting => ai,
+            Basic => ai_basic(monster_id, tcod, game, world),
+            Archer => ai_archer(monster_id, tcod, game, world),
+            Caster { cooldown } => ai_caster(monster_id, tcod, game, world, cooldown),
+            Pack => ai_pack(monster_id, tcod, game, world),
         };
-        objects[monster_id].ai = Some(new_ai);
+        world.ais.insert(monster_id, new_ai);
+    }
+    pick_up_items(monster_id, tcod, game, world);
+}
+
+// A hurt monster drinks a healing potion if it has one, which takes its turn
+fn drink_potion(monster_id: EntityId, tcod: &Tcod, game: &mut Game, world: &mut World) -> bool {
+    let hp = world.fighters.get(monster_id).map_or(0, |f| f.hp);
+    if hp as f32 >= world.max_hp(monster_id) as f32 * DRINK_HP_FRACTION {
+        return false;
+    }
+    let potion = world.inventories.get(monster_id).and_then(|inventory| {
+        inventory.iter().find_map(|&id| match world.items.get(id) {
+            Some(&Item::Heal { amount }) => Some((id, amount)),
+            _ => None,
+        })
+    });
+    let (potion_id, amount) = match potion {
+        Some(potion) => potion,
+        None => return false,
+    };
+    if in_view(monster_id, tcod, world) {
+        game.messages.add(
+            format!(
+                "The {} drinks a {}.",
+                world.names[monster_id],
+                item_name(potion_id, &game.knowledge, world)
+            ),
+            LIGHT_VIOLET,
+        );
+    }
+    let potion_id = take_one(monster_id, potion_id, game, world);
+    world.despawn(potion_id);
+    world.heal(monster_id, amount);
+    true
+}
+
+// Monsters that carry things take whatever they find where they stand
+fn pick_up_items(monster_id: EntityId, tcod: &Tcod, game: &mut Game, world: &mut World) {
+    if !world.inventories.contains(monster_id) {
+        return;
+    }
+    let (x, y) = world.pos(monster_id);
+    // Monsters have no use for food or gold
+    let found = world
+        .at(x, y)
+        .filter(|&id| match world.items.get(id) {
+            Some(Item::Food { .. }) | Some(Item::Gold) | None => false,
+            // Nor do they shoplift
+            Some(_) if world.wares.contains(id) => false,
+            Some(_) if !world.can_carry(monster_id, id) => false,
+            Some(_) => true,
+        })
+        .collect::<Vec<_>>();
+    for item_id in found {
+        if in_view(monster_id, tcod, world) {
+            game.messages.add(
+                format!(
+                    "The {} picks up a {}.",
+                    world.names[monster_id],
+                    item_name(item_id, &game.knowledge, world)
+                ),
+                LIGHT_GREY,
+            );
+        }
+        let item_id = add_to_inventory(monster_id, item_id, world);
+        equip_if_free(monster_id, item_id, world);
+    }
+}
+
+fn use_abilities(
+    monster_id: EntityId,
+    tcod: &Tcod,
+    game: &mut Game,
+    world: &mut World,
+    hunting: bool,
+) {
+    let mut abilities = match world.abilities.remove(monster_id) {
+        Some(abilities) => abilities,
+        None => return,
+    };
+    for ability in abilities.iter_mut() {
+        match ability {
+            Ability::Regeneration { hp } => world.heal(monster_id, *hp),
+            // Handled when attacking
+            Ability::LifeDrain => {}
+            Ability::Summon { wait, .. } if *wait > 0 => *wait -= 1,
+            Ability::Summon {
+                monster,
+                cooldown,
+                wait,
+            } if hunting => {
+                if summon(monster_id, monster, tcod, game, world) {
+                    *wait = *cooldown;
+                }
+            }
+            Ability::Summon { .. } => {}
+        }
+    }
+    world.abilities.insert(monster_id, abilities);
+}
+
+// Bring a new monster of the named kind next to the summoner, on the same side
+fn summon(
+    summoner_id: EntityId,
+    name: &str,
+    tcod: &Tcod,
+    game: &mut Game,
+    world: &mut World,
+) -> bool {
+    let template = match game.templates.monsters.iter().find(|t| t.name == name) {
+        Some(template) => template,
+        None => return false,
+    };
+    let (x, y) = world.pos(summoner_id);
+    let (x, y) = match free_spot_near(x, y, &game.map, world) {
+        Some(spot) => spot,
+        None => return false,
+    };
+    let monster_id = template.spawn(world, x, y, &game.templates.items);
+    let faction = world.factions[summoner_id];
+    world.factions.insert(monster_id, faction);
+    if faction == Faction::Player {
+        world.ais.insert(monster_id, Ai::Ally);
+    } else if let Some(&awareness) = world.awareness.get(summoner_id) {
+        world.awareness.insert(monster_id, awareness);
+    }
+    if tcod.fov.is_in_fov(x, y) {
+        game.messages.add(
+            format!(
+                "The {} calls forth a {}!",
+                world.names[summoner_id], world.names[monster_id]
+            ),
+            ORANGE,
+        );
     }
+    true
 }
 
 pub fn ai_confused(
-    monster_id: usize,
+    monster_id: EntityId,
     _tcod: &Tcod,
     game: &mut Game,
-    objects: &mut [Object],
+    world: &mut World,
     previous_ai: Box<Ai>,
     num_turns: i32,
 ) -> Ai {
     if num_turns >= 0 {
         // Still confused, move in a random direction and decrease the number of turns confused
-        Object::move_by(
+        world.move_by(
             monster_id,
             rand::thread_rng().gen_range(-1, 2),
             rand::thread_rng().gen_range(-1, 2),
             &game.map,
-            objects,
         );
         Ai::Confused {
             previous_ai,
@@ -120,35 +717,314 @@ pub fn ai_confused(
     } else {
         // Restore the previous AI (this one will be deleted)
         game.messages.add(
-            format!("The {} is no longer confused!", objects[monster_id].name),
+            format!("The {} is no longer confused!", world.names[monster_id]),
             RED,
         );
         *previous_ai
     }
 }
 
-pub fn ai_basic(monster_id: usize, tcod: &Tcod, game: &mut Game, objects: &mut [Object]) -> Ai {
-    // A basic monster takes it's turn. If you can see it, it can see you
-    let (monster_x, monster_y) = objects[monster_id].pos();
-    if tcod.fov.is_in_fov(monster_x, monster_y) {
-        if objects[monster_id].distance_to(&objects[PLAYER]) >= 2.0 {
-            let (player_x, player_y) = objects[PLAYER].pos();
-            move_towards(monster_id, player_x, player_y, &game.map, objects);
-        } else if objects[PLAYER].fighter.map_or(false, |f| f.hp > 0) {
-            let (monster, player) = mut_two(monster_id, PLAYER, objects);
-            monster.attack(player, game);
-        }
+pub fn ai_basic(monster_id: EntityId, _tcod: &Tcod, game: &mut Game, world: &mut World) -> Ai {
+    // A basic monster takes it's turn, attacking anything hostile next to it
+    if !attack_adjacent(monster_id, game, world) {
+        hunt(monster_id, game, world);
     }
     Ai::Basic
 }
 
-pub fn mut_two<T>(first_index: usize, second_index: usize, items: &mut [T]) -> (&mut T, &mut T) {
-    assert_ne!(first_index, second_index);
-    let split_at_index = cmp::max(first_index, second_index);
-    let (first_slice, second_slice) = items.split_at_mut(split_at_index);
-    if first_index < second_index {
-        (&mut first_slice[first_index], &mut second_slice[0])
+pub fn ai_archer(monster_id: EntityId, _tcod: &Tcod, game: &mut Game, world: &mut World) -> Ai {
+    // An archer keeps its distance and shoots whenever it has a clear shot
+    if !sees_player(monster_id, &game.map, world) {
+        hunt(monster_id, game, world);
+        return Ai::Archer;
+    }
+    let (monster_x, monster_y) = world.pos(monster_id);
+    let (player_x, player_y) = world.pos(PLAYER);
+    let distance = world.distance_to(monster_id, PLAYER);
+    if distance < ARCHER_MIN_DISTANCE && move_away(monster_id, player_x, player_y, &game.map, world)
+    {
+        return Ai::Archer;
+    }
+
+    let (hit, _) = trace_projectile(
+        (monster_x, monster_y),
+        (player_x, player_y),
+        &game.map,
+        world,
+    );
+    if distance < 2.0 {
+        // Cornered, fight back in melee
+        world.attack(monster_id, PLAYER, game);
+    } else if distance <= ARCHER_RANGE as f32 && hit == Some(PLAYER) {
+        shoot(monster_id, PLAYER, game, world);
+    } else {
+        move_towards(monster_id, player_x, player_y, &game.map, world);
+    }
+    Ai::Archer
+}
+
+fn shoot(monster_id: EntityId, target_id: EntityId, game: &mut Game, world: &mut World) {
+    let damage = world.power(monster_id) - world.defense(target_id);
+    let dealt = world.resisted_damage(target_id, damage, DamageType::Physical);
+    if dealt > 0 {
+        game.messages.add(
+            format!(
+                "The {} shoots the {} for {} hit points.",
+                world.names[monster_id], world.names[target_id], dealt
+            ),
+            WHITE,
+        );
+        world.take_damage(target_id, damage, DamageType::Physical, game);
+    } else {
+        game.messages.add(
+            format!(
+                "The {}'s arrow bounces off the {}.",
+                world.names[monster_id], world.names[target_id]
+            ),
+            LIGHT_GREY,
+        );
+    }
+}
+
+pub fn ai_caster(
+    monster_id: EntityId,
+    tcod: &Tcod,
+    game: &mut Game,
+    world: &mut World,
+    cooldown: i32,
+) -> Ai {
+    // A caster throws spells at the player when it can, and otherwise fights like a basic monster
+    if cooldown > 0 || !sees_player(monster_id, &game.map, world) {
+        ai_basic(monster_id, tcod, game, world);
+        return Ai::Caster {
+            cooldown: cmp::max(cooldown - 1, 0),
+        };
+    }
+
+    // Only consider spells that can reach the player without hurting the caster
+    let distance = world.distance_to(monster_id, PLAYER);
+    let spells = CASTER_SCROLLS
+        .iter()
+        .filter_map(|&name| game.templates.item(name))
+        .map(|scroll| scroll.effect)
+        .filter(|&spell| match spell {
+            Item::Lightning { range, .. } => distance <= range as f32,
+            Item::Confuse { range, .. } => {
+                distance <= range as f32 && !world.has_status(PLAYER, StatusKind::Confused)
+            }
+            Item::Fireball { radius, .. } => distance > radius as f32,
+            _ => false,
+        })
+        .collect::<Vec<_>>();
+    if spells.is_empty() {
+        ai_basic(monster_id, tcod, game, world);
+        return Ai::Caster { cooldown };
+    }
+
+    let spell = spells[rand::thread_rng().gen_range(0, spells.len())];
+    game.messages.add(
+        format!("The {} casts a spell!", world.names[monster_id]),
+        ORANGE,
+    );
+    match spell {
+        Item::Lightning { damage, .. } => {
+            lightning_strike(PLAYER, damage, game, world);
+        }
+        Item::Confuse { turns, .. } => confuse(PLAYER, turns, game, world),
+        Item::Fireball { damage, radius } => {
+            let (player_x, player_y) = world.pos(PLAYER);
+            fireball_blast(player_x, player_y, radius, damage, monster_id, game, world);
+        }
+        _ => {}
+    }
+    Ai::Caster {
+        cooldown: CASTER_COOLDOWN,
+    }
+}
+
+// Monsters of the same faction close by. The player's pets and shopkeepers don't count
+fn allies_nearby(monster_id: EntityId, world: &World) -> usize {
+    let faction = match world.factions.get(monster_id) {
+        Some(faction) => faction,
+        None => return 0,
+    };
+    world
+        .ais
+        .iter()
+        .filter(|&(id, _)| {
+            id != monster_id
+                && world.factions.get(id) == Some(faction)
+                && !world.is_hostile(monster_id, id)
+                && world.distance_to(id, monster_id) <= PACK_RADIUS
+        })
+        .count()
+}
+
+fn morale_breaks(monster_id: EntityId, world: &World) -> bool {
+    let hp = world.fighters.get(monster_id).map_or(0, |f| f.hp);
+    let max_hp = world.max_hp(monster_id);
+    // Monsters with friends around hold their ground for longer
+    let threshold = if allies_nearby(monster_id, world) >= 2 {
+        FLEE_HP_FRACTION / 2.0
+    } else {
+        FLEE_HP_FRACTION
+    };
+    (hp as f32) < max_hp as f32 * threshold
+}
+
+pub fn ai_fleeing(
+    monster_id: EntityId,
+    tcod: &Tcod,
+    game: &mut Game,
+    world: &mut World,
+    previous_ai: Box<Ai>,
+) -> Ai {
+    // Lick its wounds while keeping away, and return to the fight once recovered
+    world.heal(monster_id, FLEE_REGEN);
+    let hp = world.fighters.get(monster_id).map_or(0, |f| f.hp);
+    if hp as f32 >= world.max_hp(monster_id) as f32 * RETURN_HP_FRACTION {
+        if in_view(monster_id, tcod, world) {
+            game.messages.add(
+                format!("The {} regains its courage!", world.names[monster_id]),
+                ORANGE,
+            );
+        }
+        return *previous_ai;
+    }
+
+    if let Some(&Awareness::Hunting { x, y, .. }) = world.awareness.get(monster_id) {
+        let fled = move_away(monster_id, x, y, &game.map, world);
+        if !fled
+            && sees_player(monster_id, &game.map, world)
+            && world.distance_to(monster_id, PLAYER) < 2.0
+        {
+            // Cornered, lash out
+            world.attack(monster_id, PLAYER, game);
+        }
+    }
+    Ai::Fleeing { previous_ai }
+}
+
+pub fn ai_pack(monster_id: EntityId, _tcod: &Tcod, game: &mut Game, world: &mut World) -> Ai {
+    // Pack monsters call their friends and spread out around the player
+    // instead of lining up behind each other
+    if !sees_player(monster_id, &game.map, world) {
+        if !attack_adjacent(monster_id, game, world) {
+            hunt(monster_id, game, world);
+        }
+        return Ai::Pack;
+    }
+
+    let (player_x, player_y) = world.pos(PLAYER);
+    for id in world.ais.ids() {
+        if id != monster_id
+            && world.ais.get(id) == Some(&Ai::Pack)
+            && world.distance_to(id, monster_id) <= PACK_RADIUS
+        {
+            world.awareness.insert(
+                id,
+                Awareness::Hunting {
+                    x: player_x,
+                    y: player_y,
+                    turns_left: HUNT_TURNS,
+                },
+            );
+        }
+    }
+
+    if attack_adjacent(monster_id, game, world) {
+        return Ai::Pack;
+    }
+
+    // Head for the closest free spot next to the player; if there is none,
+    // wait for an opening
+    let mut openings = vec![];
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            let (x, y) = (player_x + dx, player_y + dy);
+            if (dx, dy) != (0, 0) && !is_blocked(x, y, &game.map, world) {
+                openings.push((x, y));
+            }
+        }
+    }
+    let closest = openings.into_iter().min_by(|&(x1, y1), &(x2, y2)| {
+        world
+            .distance(monster_id, x1, y1)
+            .partial_cmp(&world.distance(monster_id, x2, y2))
+            .unwrap()
+    });
+    if let Some((x, y)) = closest {
+        step_towards(monster_id, x, y, &game.map, world);
+    }
+    Ai::Pack
+}
+
+pub fn ai_ally(monster_id: EntityId, _tcod: &Tcod, game: &mut Game, world: &mut World) -> Ai {
+    // Fight whatever enemy is in sight, otherwise stay close to the player
+    match closest_enemy(monster_id, &game.map, world) {
+        Some(enemy_id) => fight(monster_id, enemy_id, game, world),
+        None => {
+            if world.distance_to(monster_id, PLAYER) > ALLY_FOLLOW_DISTANCE {
+                let (player_x, player_y) = world.pos(PLAYER);
+                step_towards(monster_id, player_x, player_y, &game.map, world);
+            }
+        }
+    }
+    Ai::Ally
+}
+
+pub fn ai_shopkeeper(
+    monster_id: EntityId,
+    _tcod: &Tcod,
+    game: &mut Game,
+    world: &mut World,
+    hunting: bool,
+) -> Ai {
+    // A robbed shopkeeper goes after the thief, otherwise it stays at its post
+    if is_angry(monster_id, world) {
+        if !attack_adjacent(monster_id, game, world) && hunting {
+            hunt(monster_id, game, world);
+        }
     } else {
-        (&mut second_slice[0], &mut first_slice[second_index])
+        let (post_x, post_y) = world.shopkeepers[monster_id].post;
+        if world.pos(monster_id) != (post_x, post_y) {
+            step_towards(monster_id, post_x, post_y, &game.map, world);
+        }
+    }
+    Ai::Shopkeeper
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spawn_monster(world: &mut World, x: i32, y: i32, ai: Ai, faction: Faction) -> EntityId {
+        let id = world.spawn_at(x, y, 'm', "monster", WHITE, true);
+        world.ais.insert(id, ai);
+        world.factions.insert(id, faction);
+        id
+    }
+
+    #[test]
+    fn other_factions_are_not_allies() {
+        let mut world = World::default();
+        let _player = world.spawn_at(5, 5, '@', "Player", WHITE, true);
+        let orc = spawn_monster(&mut world, 0, 0, Ai::Basic, Faction::Greenskin);
+        spawn_monster(&mut world, 1, 0, Ai::Ally, Faction::Player);
+        spawn_monster(&mut world, 0, 1, Ai::Basic, Faction::Troll);
+        spawn_monster(&mut world, 1, 1, Ai::Shopkeeper, Faction::Merchant);
+        assert_eq!(allies_nearby(orc, &world), 0);
+    }
+
+    #[test]
+    fn nearby_monsters_of_the_same_faction_are_allies() {
+        let mut world = World::default();
+        let _player = world.spawn_at(5, 5, '@', "Player", WHITE, true);
+        let orc = spawn_monster(&mut world, 0, 0, Ai::Basic, Faction::Greenskin);
+        spawn_monster(&mut world, 1, 0, Ai::Pack, Faction::Greenskin);
+        spawn_monster(&mut world, 0, 1, Ai::Archer, Faction::Greenskin);
+        // Too far away to count
+        spawn_monster(&mut world, 40, 40, Ai::Basic, Faction::Greenskin);
+        assert_eq!(allies_nearby(orc, &world), 2);
     }
 }
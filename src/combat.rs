@@ -0,0 +1,253 @@
+use crate::affix::OnHit;
+use crate::ai::{provokes, Ability, RAGE_TURNS};
+#[cfg(test)]
+use crate::ai::{DeathCallback, Fighter};
+use crate::damage::{apply_resistance, DamageType};
+use crate::encumbrance::BURDENED_DEFENSE_PENALTY;
+use crate::entity::{EntityId, World};
+use crate::game::{Game, COMBAT_NOISE};
+use crate::hunger::{HungerState, WEAK_POWER_PENALTY};
+use crate::status::StatusKind;
+use tcod::colors::*;
+
+impl World {
+    // Dead creatures keep their fighter only if they're the player
+    pub fn is_alive(&self, id: EntityId) -> bool {
+        self.fighters.get(id).is_some_and(|f| f.hp > 0)
+    }
+
+    pub fn power(&self, id: EntityId) -> i32 {
+        let base_power = self.fighters.get(id).map_or(0, |f| f.base_power);
+        let bonus: i32 = self.get_all_equipped(id).iter().map(|e| e.power()).sum();
+        let penalty = if self.hunger_state(id) >= HungerState::Weak {
+            WEAK_POWER_PENALTY
+        } else {
+            0
+        };
+        base_power + bonus - penalty
+    }
+
+    pub fn defense(&self, id: EntityId) -> i32 {
+        let base_defense = self.fighters.get(id).map_or(0, |f| f.base_defense);
+        let bonus: i32 = self.get_all_equipped(id).iter().map(|e| e.defense()).sum();
+        let penalty = if self.is_burdened(id) {
+            BURDENED_DEFENSE_PENALTY
+        } else {
+            0
+        };
+        base_defense + bonus - penalty
+    }
+
+    pub fn max_hp(&self, id: EntityId) -> i32 {
+        let base_max_hp = self.fighters.get(id).map_or(0, |f| f.base_max_hp);
+        let bonus: i32 = self
+            .get_all_equipped(id)
+            .iter()
+            .map(|e| e.max_hp_bonus)
+            .sum();
+        base_max_hp + bonus
+    }
+
+    pub fn resistance(&self, id: EntityId, damage_type: DamageType) -> i32 {
+        let base_resistance = self
+            .fighters
+            .get(id)
+            .map_or(0, |f| f.resistances.get(damage_type));
+        let bonus: i32 = self
+            .get_all_equipped(id)
+            .iter()
+            .map(|e| e.resistances.get(damage_type))
+            .sum();
+        base_resistance + bonus
+    }
+
+    pub fn resisted_damage(&self, id: EntityId, damage: i32, damage_type: DamageType) -> i32 {
+        apply_resistance(damage, self.resistance(id, damage_type))
+    }
+
+    pub fn heal(&mut self, id: EntityId, amount: i32) {
+        let max_hp = self.max_hp(id);
+        if let Some(fighter) = self.fighters.get_mut(id) {
+            fighter.hp += amount;
+            if fighter.hp > max_hp {
+                fighter.hp = max_hp;
+            }
+        }
+    }
+
+    pub fn take_damage(
+        &mut self,
+        id: EntityId,
+        damage: i32,
+        damage_type: DamageType,
+        game: &mut Game,
+    ) -> Option<i32> {
+        // Apply damage if possible, after resistances and vulnerabilities
+        let damage = self.resisted_damage(id, damage, damage_type);
+        self.lose_hp(id, damage, game)
+    }
+
+    // Hit points lost to something no resistance helps against, like starving.
+    // Returns the xp the creature yields if it dies
+    pub fn lose_hp(&mut self, id: EntityId, amount: i32, game: &mut Game) -> Option<i32> {
+        if let Some(fighter) = self.fighters.get_mut(id) {
+            if amount > 0 {
+                if fighter.hp - amount < 0 {
+                    fighter.hp = 0;
+                } else {
+                    fighter.hp -= amount;
+                }
+            }
+        }
+        if let Some(fighter) = self.fighters.get(id).copied() {
+            if fighter.hp == 0 {
+                self.renderables[id].always_visible = true;
+                fighter.on_death.callback(id, self, game);
+                return Some(fighter.xp);
+            }
+        }
+        None
+    }
+
+    // Extra effects of the attacker's equipment after a blow lands
+    fn apply_on_hit(&mut self, attacker: EntityId, target: EntityId, dealt: i32, game: &mut Game) {
+        let effects = self
+            .get_all_equipped(attacker)
+            .iter()
+            .filter_map(|e| e.on_hit)
+            .collect::<Vec<_>>();
+        for effect in effects {
+            match effect {
+                OnHit::Damage {
+                    damage,
+                    damage_type,
+                } => {
+                    if !self.is_alive(target) {
+                        continue;
+                    }
+                    let extra = self.resisted_damage(target, damage, damage_type);
+                    game.messages.add(
+                        format!(
+                            "{} takes {} {} damage.",
+                            self.names[target], extra, damage_type
+                        ),
+                        ORANGE,
+                    );
+                    if let Some(xp) = self.take_damage(target, damage, damage_type, game) {
+                        self.fighters[attacker].xp += xp;
+                    }
+                }
+                OnHit::LifeDrain { percent } => {
+                    self.heal(attacker, dealt * percent / 100);
+                }
+            }
+        }
+    }
+
+    pub fn attack(&mut self, attacker: EntityId, target: EntityId, game: &mut Game) {
+        // Simple attack formula for damage, melee is always physical
+        let damage = self.power(attacker) - self.defense(target);
+        let dealt = self.resisted_damage(target, damage, DamageType::Physical);
+        let (x, y) = self.pos(attacker);
+        game.make_noise(x, y, COMBAT_NOISE);
+        let attacker_faction = self.factions.get(attacker).copied();
+        if provokes(attacker_faction, self.factions.get(target).copied()) {
+            // Monsters fight back against other kinds that attack them
+            self.add_status(target, StatusKind::Enraged, RAGE_TURNS);
+        }
+        if dealt > 0 {
+            // Target takes damage
+            game.messages.add(
+                format!(
+                    "{} attacks {} for {} hp",
+                    self.names[attacker], self.names[target], dealt
+                ),
+                WHITE,
+            );
+            if let Some(xp) = self.take_damage(target, damage, DamageType::Physical, game) {
+                // Yield xp to the player
+                self.fighters[attacker].xp += xp;
+            }
+            let drains = self
+                .abilities
+                .get(attacker)
+                .is_some_and(|a| a.contains(&Ability::LifeDrain));
+            if drains {
+                self.heal(attacker, dealt);
+            }
+            self.apply_on_hit(attacker, target, dealt, game);
+        } else {
+            game.messages.add(
+                format!(
+                    "{} attacks {} but it has no effect!",
+                    self.names[attacker], self.names[target]
+                ),
+                WHITE,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+impl World {
+    // A plain 10 hp fighter carrying nothing, for tests to adjust as they need
+    pub fn spawn_test_fighter(&mut self) -> EntityId {
+        let id = self.spawn();
+        self.fighters.insert(
+            id,
+            Fighter {
+                base_max_hp: 10,
+                hp: 10,
+                base_defense: 0,
+                base_power: 0,
+                xp: 0,
+                level: 1,
+                resistances: Default::default(),
+                on_death: DeathCallback::Monster,
+            },
+        );
+        self.inventories.insert(id, vec![]);
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::damage::DamageType;
+    use crate::entity::{EntityId, World};
+    use crate::equipment::Equipment;
+
+    fn give_armor(world: &mut World, owner: EntityId, json: &str) {
+        let armor = world.spawn();
+        let equipment: Equipment = serde_json::from_str(json).unwrap();
+        world.equipment.insert(armor, equipment);
+        world.inventories[owner].push(armor);
+    }
+
+    #[test]
+    fn worn_resistances_add_to_the_fighters_own() {
+        let mut world = World::default();
+        let id = world.spawn_test_fighter();
+        world.fighters[id].resistances.fire = 25;
+        give_armor(
+            &mut world,
+            id,
+            r#"{ "slot": "Body", "equipped": true, "resistances": { "fire": 25 } }"#,
+        );
+        assert_eq!(world.resistance(id, DamageType::Fire), 50);
+        assert_eq!(world.resisted_damage(id, 20, DamageType::Fire), 10);
+        assert_eq!(world.resisted_damage(id, 20, DamageType::Cold), 20);
+    }
+
+    #[test]
+    fn carried_armor_gives_no_resistance_until_worn() {
+        let mut world = World::default();
+        let id = world.spawn_test_fighter();
+        give_armor(
+            &mut world,
+            id,
+            r#"{ "slot": "Body", "resistances": { "physical": 50 } }"#,
+        );
+        assert_eq!(world.resisted_damage(id, 20, DamageType::Physical), 20);
+    }
+}
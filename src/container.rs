@@ -0,0 +1,310 @@
+use crate::affix::roll_affixes;
+use crate::damage::DamageType;
+use crate::entity::{EntityId, World, PLAYER};
+use crate::game::{Game, Templates, COMBAT_NOISE, SPELL_NOISE};
+use crate::item::{add_to_inventory, describe, receive_item, take_from_inventory};
+use crate::log::Messages;
+use crate::map::{inventory_menu, menu, Map, Rect};
+use crate::object::is_blocked;
+use crate::shop::roll_gold;
+use crate::status::StatusKind;
+use crate::Tcod;
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use tcod::colors::*;
+
+// Chance for a room to have a chest in it, and for a chest to be locked or trapped
+const CHEST_CHANCE: f32 = 0.15;
+const LOCKED_CHANCE: f32 = 0.3;
+const TRAPPED_CHANCE: f32 = 0.2;
+const MAX_CHEST_ITEMS: i32 = 3;
+// Chance for each try at forcing a locked chest to work, and how much better the
+// odds get for each point of power
+const FORCE_CHANCE: f32 = 0.2;
+const FORCE_CHANCE_PER_POWER: f32 = 0.05;
+
+const NEEDLE_POISON_TURNS: i32 = 6;
+const EXPLOSION_DAMAGE: i32 = 15;
+const CONTAINER_MENU_WIDTH: i32 = 40;
+// How many different things fit in a chest or bag
+const CONTAINER_CAPACITY: usize = 26;
+
+// Something that holds items, like a chest on the floor or a bag in the inventory.
+// What it holds is kept in its inventory
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Container {
+    pub locked: bool,
+    pub trap: Option<Trap>,
+}
+
+// Set off by whoever opens a trapped chest, after which it's spent
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Trap {
+    PoisonNeedle,
+    Explosion,
+    Alarm,
+}
+
+// Maybe put a chest somewhere in a room, filled with a few things found at this depth.
+// It stays off the room's center, where the player starts and the stairs go, and
+// off anything else already there
+pub fn place_chest(room: Rect, map: &Map, world: &mut World, level: u32, templates: &Templates) {
+    let mut rng = rand::thread_rng();
+    if rng.gen::<f32>() >= CHEST_CHANCE {
+        return;
+    }
+    let x = rng.gen_range(room.x1 + 1, room.x2);
+    let y = rng.gen_range(room.y1 + 1, room.y2);
+    if (x, y) == room.center() || is_blocked(x, y, map, world) || world.at(x, y).next().is_some() {
+        return;
+    }
+
+    let chest = world.spawn_at(x, y, '=', "chest", BRASS, true);
+    world.renderables[chest].always_visible = true;
+    let trap = if rng.gen::<f32>() < TRAPPED_CHANCE {
+        [Trap::PoisonNeedle, Trap::Explosion, Trap::Alarm]
+            .choose(&mut rng)
+            .copied()
+    } else {
+        None
+    };
+    world.containers.insert(
+        chest,
+        Container {
+            locked: rng.gen::<f32>() < LOCKED_CHANCE,
+            trap,
+        },
+    );
+    world.inventories.insert(chest, vec![]);
+
+    let chances = templates
+        .items
+        .iter()
+        .map(|item| item.spawn_weight(level))
+        .collect::<Vec<_>>();
+    let choice = match WeightedIndex::new(&chances) {
+        Ok(choice) => choice,
+        Err(_) => return,
+    };
+    for _ in 0..rng.gen_range(1, MAX_CHEST_ITEMS + 1) {
+        let template = &templates.items[choice.sample(&mut rng)];
+        let item = template.create(world);
+        roll_affixes(item, level, &templates.affixes, world);
+        roll_gold(item, level, world);
+        add_to_inventory(chest, item, world);
+    }
+}
+
+// Get rid of a container along with everything in it
+pub fn despawn_with_contents(id: EntityId, world: &mut World) {
+    if let Some(contents) = world.inventories.remove(id) {
+        for item_id in contents {
+            despawn_with_contents(item_id, world);
+        }
+    }
+    world.despawn(id);
+}
+
+// The player bumped into a chest. Locked ones have to be forced first, and a
+// trap goes off the first time it's opened
+pub fn open_chest(chest: EntityId, tcod: &mut Tcod, game: &mut Game, world: &mut World) {
+    let (x, y) = world.pos(chest);
+    if world.containers[chest].locked {
+        game.make_noise(x, y, COMBAT_NOISE);
+        let power = world.power(PLAYER) as f32;
+        if rand::random::<f32>() >= FORCE_CHANCE + FORCE_CHANCE_PER_POWER * power {
+            game.messages.add(
+                "The chest is locked. You try to force it, but it holds.",
+                WHITE,
+            );
+            return;
+        }
+        game.messages
+            .add("You force the lock and the chest springs open!", YELLOW);
+        world.containers[chest].locked = false;
+    }
+    if let Some(trap) = world.containers[chest].trap.take() {
+        spring_trap(trap, x, y, game, world);
+        if !world.is_alive(PLAYER) {
+            return;
+        }
+    }
+    open_container(chest, tcod, game, world);
+}
+
+fn spring_trap(trap: Trap, x: i32, y: i32, game: &mut Game, world: &mut World) {
+    match trap {
+        Trap::PoisonNeedle => {
+            game.messages
+                .add("A needle pricks your finger. It was poisoned!", RED);
+            world.add_status(PLAYER, StatusKind::Poisoned, NEEDLE_POISON_TURNS);
+        }
+        Trap::Explosion => {
+            game.messages
+                .add("The chest explodes in your face!", ORANGE);
+            world.take_damage(PLAYER, EXPLOSION_DAMAGE, DamageType::Fire, game);
+        }
+        Trap::Alarm => {
+            game.messages
+                .add("A loud bell rings out from inside the chest!", YELLOW);
+            game.make_noise(x, y, SPELL_NOISE * 2);
+        }
+    }
+}
+
+// Let the player move things in and out of a container until they're done
+pub fn open_container(container: EntityId, tcod: &mut Tcod, game: &mut Game, world: &mut World) {
+    loop {
+        let contents = world.inventories[container].len();
+        let header = format!(
+            "The {} holds {} thing{}.\n",
+            world.names[container],
+            contents,
+            if contents == 1 { "" } else { "s" }
+        );
+        match menu(
+            &header,
+            &["Take something out", "Put something in"],
+            CONTAINER_MENU_WIDTH,
+            &mut tcod.root,
+        ) {
+            Some(0) => take_out(container, tcod, game, world),
+            Some(1) => put_in(container, tcod, game, world),
+            _ => return,
+        }
+    }
+}
+
+fn take_out(container: EntityId, tcod: &mut Tcod, game: &mut Game, world: &mut World) {
+    let item_id = match inventory_menu(
+        &world.inventories[container].clone(),
+        &game.knowledge,
+        world,
+        "Press the key next to an item to take it, or any other to go back.\n",
+        &mut tcod.root,
+    ) {
+        Some(item_id) => item_id,
+        None => return,
+    };
+    receive_item(item_id, Some(container), game, world);
+}
+
+// Whether an item can go in a container, saying why not if it can't
+fn can_put_in(
+    container: EntityId,
+    item_id: EntityId,
+    messages: &mut Messages,
+    world: &World,
+) -> bool {
+    if world.containers.contains(item_id) {
+        messages.add(
+            format!(
+                "The {} won't fit inside the {}.",
+                world.names[item_id], world.names[container]
+            ),
+            RED,
+        );
+        return false;
+    }
+    if world.inventories[container].len() >= CONTAINER_CAPACITY {
+        messages.add(format!("The {} is full.", world.names[container]), RED);
+        return false;
+    }
+    world.can_remove(item_id, messages)
+}
+
+fn put_in(container: EntityId, tcod: &mut Tcod, game: &mut Game, world: &mut World) {
+    // Containers don't go inside each other
+    let choices = world.inventories[PLAYER]
+        .iter()
+        .cloned()
+        .filter(|&id| !world.containers.contains(id))
+        .collect::<Vec<_>>();
+    let item_id = match inventory_menu(
+        &choices,
+        &game.knowledge,
+        world,
+        "Press the key next to an item to put it in, or any other to go back.\n",
+        &mut tcod.root,
+    ) {
+        Some(item_id) => item_id,
+        None => return,
+    };
+    if !can_put_in(container, item_id, &mut game.messages, world) {
+        return;
+    }
+    game.messages.add(
+        format!(
+            "You put {} in the {}.",
+            describe(item_id, &game.knowledge, world),
+            world.names[container]
+        ),
+        WHITE,
+    );
+    take_from_inventory(PLAYER, item_id, game, world);
+    add_to_inventory(container, item_id, world);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equipment::Equipment;
+
+    fn spawn_bag(world: &mut World) -> EntityId {
+        let id = world.spawn();
+        world.names.insert(id, "bag".into());
+        world.containers.insert(
+            id,
+            Container {
+                locked: false,
+                trap: None,
+            },
+        );
+        world.inventories.insert(id, vec![]);
+        id
+    }
+
+    fn spawn_thing(world: &mut World, name: &str) -> EntityId {
+        let id = world.spawn();
+        world.names.insert(id, name.into());
+        id
+    }
+
+    #[test]
+    fn containers_do_not_go_inside_each_other() {
+        let mut world = World::default();
+        let mut messages = Messages::new();
+        let bag = spawn_bag(&mut world);
+        let other = spawn_bag(&mut world);
+        let rock = spawn_thing(&mut world, "rock");
+        assert!(can_put_in(bag, rock, &mut messages, &world));
+        assert!(!can_put_in(bag, other, &mut messages, &world));
+    }
+
+    #[test]
+    fn a_full_container_takes_nothing_more() {
+        let mut world = World::default();
+        let mut messages = Messages::new();
+        let bag = spawn_bag(&mut world);
+        for _ in 0..CONTAINER_CAPACITY {
+            let rock = spawn_thing(&mut world, "rock");
+            world.inventories[bag].push(rock);
+        }
+        let rock = spawn_thing(&mut world, "rock");
+        assert!(!can_put_in(bag, rock, &mut messages, &world));
+    }
+
+    #[test]
+    fn worn_cursed_items_stay_out() {
+        let mut world = World::default();
+        let mut messages = Messages::new();
+        let bag = spawn_bag(&mut world);
+        let helmet = spawn_thing(&mut world, "helmet");
+        let equipment: Equipment =
+            serde_json::from_str(r#"{ "slot": "Head", "equipped": true, "cursed": true }"#)
+                .unwrap();
+        world.equipment.insert(helmet, equipment);
+        assert!(!can_put_in(bag, helmet, &mut messages, &world));
+    }
+}
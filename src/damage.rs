@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DamageType {
+    Physical,
+    Fire,
+    Lightning,
+    Cold,
+    Poison,
+}
+
+impl DamageType {
+    pub const ALL: [DamageType; 5] = [
+        DamageType::Physical,
+        DamageType::Fire,
+        DamageType::Lightning,
+        DamageType::Cold,
+        DamageType::Poison,
+    ];
+}
+
+impl std::fmt::Display for DamageType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            DamageType::Physical => write!(f, "physical"),
+            DamageType::Fire => write!(f, "fire"),
+            DamageType::Lightning => write!(f, "lightning"),
+            DamageType::Cold => write!(f, "cold"),
+            DamageType::Poison => write!(f, "poison"),
+        }
+    }
+}
+
+// Percentage of damage ignored per damage type. 100 is immune,
+// negative values are vulnerabilities (-100 takes double damage)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Resistances {
+    pub physical: i32,
+    pub fire: i32,
+    pub lightning: i32,
+    pub cold: i32,
+    pub poison: i32,
+}
+
+impl Resistances {
+    pub fn get(&self, damage_type: DamageType) -> i32 {
+        match damage_type {
+            DamageType::Physical => self.physical,
+            DamageType::Fire => self.fire,
+            DamageType::Lightning => self.lightning,
+            DamageType::Cold => self.cold,
+            DamageType::Poison => self.poison,
+        }
+    }
+}
+
+impl std::ops::AddAssign for Resistances {
+    fn add_assign(&mut self, other: Resistances) {
+        self.physical += other.physical;
+        self.fire += other.fire;
+        self.lightning += other.lightning;
+        self.cold += other.cold;
+        self.poison += other.poison;
+    }
+}
+
+pub fn apply_resistance(damage: i32, resistance: i32) -> i32 {
+    // Resistances can't heal, so cap them at immunity
+    let resistance = resistance.min(100);
+    (damage * (100 - resistance) / 100).max(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resistance_reduces_damage_by_percentage() {
+        assert_eq!(apply_resistance(20, 0), 20);
+        assert_eq!(apply_resistance(20, 50), 10);
+        assert_eq!(apply_resistance(20, -100), 40);
+    }
+
+    #[test]
+    fn resistance_is_capped_at_immunity() {
+        assert_eq!(apply_resistance(20, 100), 0);
+        assert_eq!(apply_resistance(20, 250), 0);
+    }
+
+    #[test]
+    fn resistances_add_up_per_type() {
+        let mut resistances = Resistances {
+            fire: 50,
+            cold: -50,
+            ..Default::default()
+        };
+        resistances += Resistances {
+            fire: 25,
+            poison: 100,
+            ..Default::default()
+        };
+        assert_eq!(resistances.get(DamageType::Fire), 75);
+        assert_eq!(resistances.get(DamageType::Cold), -50);
+        assert_eq!(resistances.get(DamageType::Poison), 100);
+        assert_eq!(resistances.get(DamageType::Physical), 0);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::entity::{EntityId, World};
+use crate::item::count;
+
+// How much a creature can carry without being slowed down, in pounds
+const BASE_CAPACITY: f32 = 40.0;
+const CAPACITY_PER_POWER: f32 = 10.0;
+// Nothing more can be picked up past this many times the capacity
+const HARD_LIMIT_FACTOR: f32 = 1.5;
+
+// Defense lost while carrying more than the capacity
+pub const BURDENED_DEFENSE_PENALTY: i32 = 2;
+
+// How much a corpse weighs for each hit point its owner had
+pub const CORPSE_WEIGHT_PER_HP: f32 = 0.5;
+
+impl World {
+    // The weight of an item, counting every item in its stack and anything inside it
+    pub fn weight(&self, id: EntityId) -> f32 {
+        let own = self
+            .weights
+            .get(id)
+            .map_or(0.0, |&w| w * count(id, self) as f32);
+        own + self.carried_weight(id)
+    }
+
+    // Everything a creature has on it, worn or not, or everything in a container
+    pub fn carried_weight(&self, id: EntityId) -> f32 {
+        self.inventories.get(id).map_or(0.0, |inventory| {
+            inventory.iter().map(|&i| self.weight(i)).sum()
+        })
+    }
+
+    // Stronger creatures can carry more
+    pub fn carry_capacity(&self, id: EntityId) -> f32 {
+        let base_power = self.fighters.get(id).map_or(0, |f| f.base_power);
+        BASE_CAPACITY + CAPACITY_PER_POWER * base_power as f32
+    }
+
+    pub fn is_burdened(&self, id: EntityId) -> bool {
+        self.carried_weight(id) > self.carry_capacity(id)
+    }
+
+    // Whether picking up an item would stay under the hard limit
+    pub fn can_carry(&self, id: EntityId, item_id: EntityId) -> bool {
+        self.carried_weight(id) + self.weight(item_id)
+            <= self.carry_capacity(id) * HARD_LIMIT_FACTOR
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entity::{EntityId, World};
+    use crate::item::Stack;
+
+    fn spawn_weight(world: &mut World, weight: f32, count: i32) -> EntityId {
+        let id = world.spawn();
+        world.weights.insert(id, weight);
+        world.stacks.insert(id, Stack { count });
+        id
+    }
+
+    #[test]
+    fn carried_weight_counts_stacks_and_what_is_in_bags() {
+        let mut world = World::default();
+        let owner = world.spawn_test_fighter();
+        let arrows = spawn_weight(&mut world, 0.5, 10);
+        let bag = spawn_weight(&mut world, 1.0, 1);
+        let rock = spawn_weight(&mut world, 3.0, 1);
+        world.inventories.insert(bag, vec![rock]);
+        world.inventories[owner].extend(&[arrows, bag]);
+        assert_eq!(world.weight(bag), 4.0);
+        assert_eq!(world.carried_weight(owner), 9.0);
+    }
+
+    #[test]
+    fn can_carry_up_to_the_hard_limit() {
+        let mut world = World::default();
+        let owner = world.spawn_test_fighter();
+        world.fighters[owner].base_power = 2;
+        // A capacity of 60 pounds, and a hard limit of 90
+        assert_eq!(world.carry_capacity(owner), 60.0);
+        let load = spawn_weight(&mut world, 70.0, 1);
+        world.inventories[owner].push(load);
+        assert!(world.is_burdened(owner));
+        let light = spawn_weight(&mut world, 20.0, 1);
+        let heavy = spawn_weight(&mut world, 20.5, 1);
+        assert!(world.can_carry(owner, light));
+        assert!(!world.can_carry(owner, heavy));
+    }
+}
@@ -0,0 +1,274 @@
+use crate::ai::{Ability, Ai, Awareness, Faction, Fighter, Vision};
+use crate::container::Container;
+use crate::equipment::Equipment;
+use crate::hunger::Hunger;
+use crate::item::{Charges, Item, Stack};
+use crate::monster::Unique;
+use crate::object::{Blocks, Position, Renderable};
+use crate::ranged::{Ammo, RangedWeapon};
+use crate::shop::{Merchandise, Purse, Shopkeeper};
+use crate::status::Status;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::{Index, IndexMut};
+
+// A handle to an entity that stays valid while other entities come and go
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct EntityId(u32);
+
+// The player is always the first entity to be spawned
+pub const PLAYER: EntityId = EntityId(0);
+
+// All components of one kind, by the entity they belong to. Iterating goes
+// in the order the entities were spawned
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Storage<T> {
+    components: BTreeMap<EntityId, T>,
+}
+
+impl<T> Default for Storage<T> {
+    fn default() -> Self {
+        Storage {
+            components: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T> Storage<T> {
+    pub fn get(&self, id: EntityId) -> Option<&T> {
+        self.components.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut T> {
+        self.components.get_mut(&id)
+    }
+
+    pub fn insert(&mut self, id: EntityId, component: T) -> Option<T> {
+        self.components.insert(id, component)
+    }
+
+    pub fn remove(&mut self, id: EntityId) -> Option<T> {
+        self.components.remove(&id)
+    }
+
+    pub fn contains(&self, id: EntityId) -> bool {
+        self.components.contains_key(&id)
+    }
+
+    // A snapshot of the entities that have this component, for loops that
+    // add or remove entities as they go
+    pub fn ids(&self) -> Vec<EntityId> {
+        self.components.keys().cloned().collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &T)> {
+        self.components
+            .iter()
+            .map(|(&id, component)| (id, component))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (EntityId, &mut T)> {
+        self.components
+            .iter_mut()
+            .map(|(&id, component)| (id, component))
+    }
+
+    fn retain<F: Fn(EntityId) -> bool>(&mut self, keep: F) {
+        self.components.retain(|&id, _| keep(id));
+    }
+}
+
+impl<T: Default> Storage<T> {
+    pub fn get_or_insert_default(&mut self, id: EntityId) -> &mut T {
+        self.components.entry(id).or_default()
+    }
+}
+
+impl<T> Index<EntityId> for Storage<T> {
+    type Output = T;
+
+    fn index(&self, id: EntityId) -> &T {
+        &self.components[&id]
+    }
+}
+
+impl<T> IndexMut<EntityId> for Storage<T> {
+    fn index_mut(&mut self, id: EntityId) -> &mut T {
+        self.components
+            .get_mut(&id)
+            .expect("entity has no such component")
+    }
+}
+
+// Declares the world with one storage per kind of component. A new kind of
+// component only needs a line in the list below
+macro_rules! world {
+    ($($name:ident: $component:ty,)*) => {
+        #[derive(Default, Serialize, Deserialize)]
+        pub struct World {
+            next_id: u32,
+            $(pub $name: Storage<$component>,)*
+        }
+
+        impl World {
+            // Remove an entity along with all of its components
+            pub fn despawn(&mut self, id: EntityId) {
+                $(self.$name.remove(id);)*
+            }
+
+            // Make a new entity with a copy of each of another one's components
+            pub fn duplicate(&mut self, id: EntityId) -> EntityId {
+                let copy = self.spawn();
+                $(
+                    if let Some(component) = self.$name.get(id).cloned() {
+                        self.$name.insert(copy, component);
+                    }
+                )*
+                copy
+            }
+
+            fn retain<F: Fn(EntityId) -> bool>(&mut self, keep: F) {
+                $(self.$name.retain(&keep);)*
+            }
+        }
+    };
+}
+
+world! {
+    positions: Position,
+    renderables: Renderable,
+    names: String,
+    blockers: Blocks,
+    fighters: Fighter,
+    ais: Ai,
+    awareness: Awareness,
+    visions: Vision,
+    factions: Faction,
+    items: Item,
+    equipment: Equipment,
+    ranged: RangedWeapon,
+    ammo: Ammo,
+    stacks: Stack,
+    charges: Charges,
+    statuses: Vec<Status>,
+    abilities: Vec<Ability>,
+    uniques: Unique,
+    inventories: Vec<EntityId>,
+    hungers: Hunger,
+    purses: Purse,
+    // What an item sells for in a shop, before its enchantment and affixes
+    prices: i32,
+    // How heavy one item is, in pounds
+    weights: f32,
+    shopkeepers: Shopkeeper,
+    wares: Merchandise,
+    containers: Container,
+}
+
+impl World {
+    // A new entity without any components. IDs are never reused
+    pub fn spawn(&mut self) -> EntityId {
+        let id = EntityId(self.next_id);
+        self.next_id += 1;
+        id
+    }
+
+    // Remove everything except the given entities and whatever they carry
+    pub fn keep_only(&mut self, survivors: &[EntityId]) {
+        let mut kept = BTreeSet::new();
+        let mut to_visit = survivors.to_vec();
+        while let Some(id) = to_visit.pop() {
+            if kept.insert(id) {
+                if let Some(inventory) = self.inventories.get(id) {
+                    to_visit.extend(inventory.iter().cloned());
+                }
+            }
+        }
+        self.retain(|id| kept.contains(&id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn storage_iterates_in_spawn_order() {
+        let mut world = World::default();
+        let first = world.spawn();
+        let second = world.spawn();
+        let third = world.spawn();
+        world.names.insert(third, "third".into());
+        world.names.insert(first, "first".into());
+        world.names.insert(second, "second".into());
+        assert_eq!(world.names.ids(), vec![first, second, third]);
+        assert_eq!(world.names.remove(second), Some("second".into()));
+        assert!(!world.names.contains(second));
+        assert_eq!(world.names.ids(), vec![first, third]);
+    }
+
+    #[test]
+    fn ids_are_never_reused() {
+        let mut world = World::default();
+        let first = world.spawn();
+        world.despawn(first);
+        assert_ne!(world.spawn(), first);
+    }
+
+    #[test]
+    fn get_or_insert_default_keeps_existing_components() {
+        let mut world = World::default();
+        let rich = world.spawn();
+        let poor = world.spawn();
+        world.purses.insert(rich, Purse { gold: 50 });
+        world.purses.get_or_insert_default(rich).gold += 1;
+        world.purses.get_or_insert_default(poor).gold += 1;
+        assert_eq!(world.purses[rich].gold, 51);
+        assert_eq!(world.purses[poor].gold, 1);
+    }
+
+    #[test]
+    fn despawn_removes_every_component() {
+        let mut world = World::default();
+        let id = world.spawn();
+        world.names.insert(id, "orc".into());
+        world.inventories.insert(id, vec![]);
+        world.despawn(id);
+        assert!(!world.names.contains(id));
+        assert!(!world.inventories.contains(id));
+    }
+
+    #[test]
+    fn duplicate_copies_components_to_a_new_entity() {
+        let mut world = World::default();
+        let id = world.spawn();
+        world.names.insert(id, "arrows".into());
+        world.stacks.insert(id, Stack { count: 3 });
+        let copy = world.duplicate(id);
+        assert_ne!(copy, id);
+        assert_eq!(world.names[copy], "arrows");
+        assert_eq!(world.stacks[copy].count, 3);
+        world.stacks[copy].count = 1;
+        assert_eq!(world.stacks[id].count, 3);
+    }
+
+    #[test]
+    fn keep_only_keeps_survivors_and_everything_they_carry() {
+        let mut world = World::default();
+        let player = world.spawn();
+        let bag = world.spawn();
+        let potion = world.spawn();
+        let stranger = world.spawn();
+        let strangers_sword = world.spawn();
+        world.inventories.insert(player, vec![bag]);
+        world.inventories.insert(bag, vec![potion]);
+        world.inventories.insert(stranger, vec![strangers_sword]);
+        for id in [player, bag, potion, stranger, strangers_sword].iter() {
+            world.names.insert(*id, "thing".into());
+        }
+        world.keep_only(&[player]);
+        assert_eq!(world.names.ids(), vec![player, bag, potion]);
+        assert!(!world.inventories.contains(stranger));
+    }
+}
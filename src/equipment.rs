@@ -1,22 +1,107 @@
-use crate::object::Object;
+use crate::affix::{OnHit, Rarity};
+use crate::damage::Resistances;
+use crate::entity::{EntityId, World};
+use crate::log::Messages;
 use serde::{Deserialize, Serialize};
+use tcod::colors::*;
 
 // An object that can be equipped, yielding bonuses
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Equipment {
     pub slot: Slot,
+    #[serde(default)]
     pub equipped: bool,
-    // Can be negative if an item is cursed
+    #[serde(default)]
     pub power_bonus: i32,
+    #[serde(default)]
     pub defense_bonus: i32,
+    #[serde(default)]
     pub max_hp_bonus: i32,
+    #[serde(default)]
+    pub resistances: Resistances,
+    // Wielded in the right hand, leaving the left hand unusable
+    #[serde(default)]
+    pub two_handed: bool,
+    // Added to the power of weapons and the defense of anything else. Usually
+    // negative if the item is cursed
+    #[serde(default)]
+    pub enchantment: i32,
+    // Can't be taken off once equipped
+    #[serde(default)]
+    pub cursed: bool,
+    // The enchantment shows in the item's name once it has been worn
+    #[serde(default)]
+    pub known: bool,
+    #[serde(default)]
+    pub rarity: Rarity,
+    #[serde(default)]
+    pub on_hit: Option<OnHit>,
 }
 
+impl Equipment {
+    // Weapons are enchanted for attack, anything else for defense
+    fn enchants_power(&self) -> bool {
+        self.slot == Slot::RightHand || self.slot == Slot::Ranged
+    }
+
+    // Melee power. A ranged weapon's enchantment only counts when it's fired
+    pub fn power(&self) -> i32 {
+        if self.slot == Slot::RightHand {
+            self.power_bonus + self.enchantment
+        } else {
+            self.power_bonus
+        }
+    }
+
+    pub fn defense(&self) -> i32 {
+        if self.enchants_power() {
+            self.defense_bonus
+        } else {
+            self.defense_bonus + self.enchantment
+        }
+    }
+
+    pub fn slots(&self) -> Vec<Slot> {
+        if self.two_handed {
+            vec![self.slot, Slot::LeftHand]
+        } else {
+            vec![self.slot]
+        }
+    }
+}
+
+// The right hand holds weapons and the left one shields. Rings are listed as
+// "Ring" in the data file and go on whichever hand is free
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Slot {
     LeftHand,
     RightHand,
     Head,
+    Body,
+    Cloak,
+    Gloves,
+    Boots,
+    #[serde(alias = "Ring")]
+    LeftRing,
+    RightRing,
+    Amulet,
+    Ranged,
+}
+
+impl Slot {
+    pub const ALL: [Slot; 11] = [
+        Slot::Head,
+        Slot::Amulet,
+        Slot::Body,
+        Slot::Cloak,
+        Slot::RightHand,
+        Slot::LeftHand,
+        Slot::Gloves,
+        Slot::LeftRing,
+        Slot::RightRing,
+        Slot::Boots,
+        Slot::Ranged,
+    ];
 }
 
 impl std::fmt::Display for Slot {
@@ -25,19 +110,168 @@ impl std::fmt::Display for Slot {
             Slot::LeftHand => write!(f, "left hand"),
             Slot::RightHand => write!(f, "right hand"),
             Slot::Head => write!(f, "head"),
+            Slot::Body => write!(f, "body"),
+            Slot::Cloak => write!(f, "shoulders"),
+            Slot::Gloves => write!(f, "hands"),
+            Slot::Boots => write!(f, "feet"),
+            Slot::LeftRing => write!(f, "left ring finger"),
+            Slot::RightRing => write!(f, "right ring finger"),
+            Slot::Amulet => write!(f, "neck"),
+            Slot::Ranged => write!(f, "ranged"),
         }
     }
 }
 
-pub fn get_equipped_in_slot(slot: Slot, inventory: &[Object]) -> Option<usize> {
-    for (inventory_id, item) in inventory.iter().enumerate() {
-        if item
+pub fn get_equipped_in_slot(slot: Slot, owner: EntityId, world: &World) -> Option<EntityId> {
+    let inventory = world.inventories.get(owner)?;
+    inventory.iter().cloned().find(|&item_id| {
+        world
             .equipment
-            .as_ref()
-            .map_or(false, |e| e.equipped && e.slot == slot)
-        {
-            return Some(inventory_id);
+            .get(item_id)
+            .is_some_and(|e| e.equipped && e.slots().contains(&slot))
+    })
+}
+
+// Get an item ready to be put on, moving a ring to a free ring finger if there
+// is one. Returns whatever worn items are in the way
+pub fn claim_slot(owner: EntityId, item_id: EntityId, world: &mut World) -> Vec<EntityId> {
+    let slot = world.equipment[item_id].slot;
+    if slot == Slot::LeftRing || slot == Slot::RightRing {
+        let free = [Slot::LeftRing, Slot::RightRing]
+            .iter()
+            .cloned()
+            .find(|&ring| get_equipped_in_slot(ring, owner, world).is_none());
+        if let Some(free) = free {
+            world.equipment[item_id].slot = free;
+        }
+    }
+    let mut in_the_way = vec![];
+    for slot in world.equipment[item_id].slots() {
+        if let Some(worn) = get_equipped_in_slot(slot, owner, world) {
+            if worn != item_id && !in_the_way.contains(&worn) {
+                in_the_way.push(worn);
+            }
         }
     }
-    None
+    in_the_way
+}
+
+impl World {
+    // Whatever the entity has equipped out of what it carries
+    pub fn get_all_equipped(&self, owner: EntityId) -> Vec<Equipment> {
+        match self.inventories.get(owner) {
+            Some(inventory) => inventory
+                .iter()
+                .filter_map(|&item_id| self.equipment.get(item_id))
+                .filter(|e| e.equipped)
+                .cloned()
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    pub fn equip(&mut self, item_id: EntityId, messages: &mut Messages) {
+        if !self.items.contains(item_id) {
+            messages.add(
+                format!(
+                    "Can't equip {} because it's not an Item.",
+                    self.names[item_id]
+                ),
+                RED,
+            );
+            return;
+        }
+        if let Some(equipment) = self.equipment.get_mut(item_id) {
+            if !equipment.equipped {
+                equipment.equipped = true;
+                equipment.known = true;
+                messages.add(
+                    format!("Equipped {} on {}", self.names[item_id], equipment.slot),
+                    LIGHT_GREEN,
+                );
+                if equipment.cursed {
+                    messages.add(
+                        format!(
+                            "The {} binds itself to you. It is cursed!",
+                            self.names[item_id]
+                        ),
+                        RED,
+                    );
+                }
+            }
+        } else {
+            messages.add(
+                format!(
+                    "Can't equip {} because it's not an Equipment Item.",
+                    self.names[item_id]
+                ),
+                RED,
+            );
+        }
+    }
+
+    // Whether a carried item can be put away, complaining if it's a cursed one
+    // that is being worn
+    pub fn can_remove(&self, item_id: EntityId, messages: &mut Messages) -> bool {
+        match self.equipment.get(item_id) {
+            Some(equipment) if equipment.equipped && equipment.cursed => {
+                messages.add(
+                    format!("You can't remove the cursed {}!", self.names[item_id]),
+                    RED,
+                );
+                false
+            }
+            _ => true,
+        }
+    }
+
+    pub fn dequip(&mut self, item_id: EntityId, messages: &mut Messages) {
+        if !self.items.contains(item_id) {
+            messages.add(
+                format!(
+                    "Can't dequip {} because it's not an Item.",
+                    self.names[item_id]
+                ),
+                RED,
+            );
+            return;
+        }
+        if let Some(equipment) = self.equipment.get_mut(item_id) {
+            if equipment.equipped {
+                equipment.equipped = false;
+                messages.add(
+                    format!("Dequipped {} on {}", self.names[item_id], equipment.slot),
+                    LIGHT_YELLOW,
+                );
+            }
+        } else {
+            messages.add(
+                format!(
+                    "Can't dequip {} because it's not an Equipment Item.",
+                    self.names[item_id]
+                ),
+                RED,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn equipment(json: &str) -> Equipment {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn enchantment_goes_to_attack_for_weapons_and_defense_otherwise() {
+        let sword = equipment(r#"{ "slot": "RightHand", "power_bonus": 3, "enchantment": 2 }"#);
+        assert_eq!((sword.power(), sword.defense()), (5, 0));
+        let shield = equipment(r#"{ "slot": "LeftHand", "defense_bonus": 1, "enchantment": 2 }"#);
+        assert_eq!((shield.power(), shield.defense()), (0, 3));
+        // Only counted when the bow is fired
+        let bow = equipment(r#"{ "slot": "Ranged", "enchantment": 2 }"#);
+        assert_eq!((bow.power(), bow.defense()), (0, 0));
+    }
 }
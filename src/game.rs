@@ -1,34 +1,120 @@
+use crate::affix::{AffixTemplate, AFFIXES_FILE};
+use crate::container::place_chest;
+use crate::entity::{EntityId, World, PLAYER};
+use crate::identify::Knowledge;
 use crate::log::Messages;
+use crate::loot::{ItemTemplate, ITEMS_FILE};
 use crate::map::*;
-use crate::object::{place_objects, Object};
+use crate::monster::{place_uniques, MonsterTemplate, UniqueTemplate, MONSTERS_FILE, UNIQUES_FILE};
+use crate::object::place_objects;
+use crate::shop::place_shop;
 use rand::Rng;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
 use tcod::colors::*;
 
+// How far away various actions can be heard
+pub const MOVE_NOISE: i32 = 2;
+pub const IMPACT_NOISE: i32 = 4;
+pub const COMBAT_NOISE: i32 = 8;
+pub const SPELL_NOISE: i32 = 10;
+
+fn read_data<T: DeserializeOwned>(path: &str) -> Result<T, Box<dyn Error>> {
+    let mut json = String::new();
+    let mut file = File::open(path)?;
+    file.read_to_string(&mut json)?;
+    let data = serde_json::from_str::<T>(&json)?;
+    Ok(data)
+}
+
+// The game can't run without its data files, so give up if one is missing or broken
+fn load_data<T: DeserializeOwned>(path: &str) -> T {
+    read_data(path).unwrap_or_else(|e| panic!("Failed to load {}: {}", path, e))
+}
+
+// Monster and item definitions, loaded from the data files rather than saved with the game
+#[derive(Default)]
+pub struct Templates {
+    pub monsters: Vec<MonsterTemplate>,
+    pub uniques: Vec<UniqueTemplate>,
+    pub items: Vec<ItemTemplate>,
+    pub affixes: Vec<AffixTemplate>,
+}
+
+impl Templates {
+    pub fn load() -> Self {
+        Templates {
+            monsters: load_data(MONSTERS_FILE),
+            uniques: load_data(UNIQUES_FILE),
+            items: load_data(ITEMS_FILE),
+            affixes: load_data(AFFIXES_FILE),
+        }
+    }
+
+    pub fn item(&self, name: &str) -> Option<&ItemTemplate> {
+        self.items.iter().find(|t| t.name == name)
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Game {
     pub map: Map,
     pub messages: Messages,
-    pub inventory: Vec<Object>,
     pub dungeon_level: u32,
+    // Uniques that have already appeared this run, and what became of them
+    pub uniques_spawned: Vec<String>,
+    pub history: Vec<String>,
+    pub knowledge: Knowledge,
+    // Turns the player has taken this run
+    pub turns: u32,
+    // Noises made since the monsters last acted
+    #[serde(skip)]
+    pub noises: Vec<Noise>,
+    #[serde(skip)]
+    pub templates: Templates,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Noise {
+    pub x: i32,
+    pub y: i32,
+    pub loudness: i32,
 }
 
 impl Game {
-    pub fn new(objects: &mut Vec<Object>) -> Self {
-        Game {
-            map: Game::make_map(objects, 1),
+    pub fn new(world: &mut World) -> Self {
+        let templates = Templates::load();
+        let mut game = Game {
+            map: Game::make_map(world, &[PLAYER], 1, &templates),
             messages: Messages::new(),
-            inventory: vec![],
             dungeon_level: 1,
-        }
+            uniques_spawned: vec![],
+            history: vec![],
+            knowledge: Knowledge::new(&templates.items),
+            turns: 0,
+            noises: vec![],
+            templates,
+        };
+        place_uniques(&mut game, world);
+        game
+    }
+
+    pub fn make_noise(&mut self, x: i32, y: i32, loudness: i32) {
+        self.noises.push(Noise { x, y, loudness });
     }
 
-    pub fn make_map(objects: &mut Vec<Object>, level: u32) -> Map {
+    pub fn make_map(
+        world: &mut World,
+        survivors: &[EntityId],
+        level: u32,
+        templates: &Templates,
+    ) -> Map {
         let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
-        // Player is the first element, remove everything else.
-        // NOTE: works only when the player is the first object!
-        assert_eq!(&objects[PLAYER] as *const _, &objects[0] as *const _);
-        objects.truncate(1);
+        // Keep the player and whoever comes along, remove everything else
+        world.keep_only(survivors);
         let mut rooms = vec![];
 
         for _ in 0..MAX_ROOMS {
@@ -48,14 +134,14 @@ impl Game {
 
             if !failed {
                 create_room(new_room, &mut map);
-                place_objects(new_room, &map, objects, level);
+                place_objects(new_room, &map, world, level, templates);
 
                 // Center coordinates of the new room
                 let (new_x, new_y) = new_room.center();
 
                 if rooms.is_empty() {
                     // Set the player here
-                    objects[PLAYER].set_pos(new_x, new_y);
+                    world.set_pos(PLAYER, new_x, new_y);
                 } else {
                     // Connect all rooms that aren't the first room with tunnels
 
@@ -80,10 +166,33 @@ impl Game {
 
         // Create stairs at the center of the last room
         let (last_room_x, last_room_y) = rooms[rooms.len() - 1].center();
-        let mut stairs = Object::new(last_room_x, last_room_y, '<', "Stairs", WHITE, false);
-        stairs.always_visible = true;
-        objects.push(stairs);
+        let stairs = world.spawn_at(last_room_x, last_room_y, '<', "Stairs", WHITE, false);
+        world.renderables[stairs].always_visible = true;
+
+        // Chests go in once the stairs and the player are in place, so they can't block either
+        for &room in &rooms {
+            place_chest(room, &map, world, level, templates);
+        }
+        place_shop(&rooms, &map, world, level, templates);
 
         map
     }
 }
+
+#[cfg(test)]
+impl Game {
+    // An open level with nothing on it and no templates, for tests
+    pub fn empty() -> Self {
+        Game {
+            map: vec![vec![Tile::empty(); MAP_HEIGHT as usize]; MAP_WIDTH as usize],
+            messages: Messages::new(),
+            dungeon_level: 1,
+            uniques_spawned: vec![],
+            history: vec![],
+            knowledge: Knowledge::new(&[]),
+            turns: 0,
+            noises: vec![],
+            templates: Templates::default(),
+        }
+    }
+}
@@ -1,6 +1,10 @@
+use crate::entity::{World, PLAYER};
+use crate::map::Map;
+use crate::status::StatusKind;
 use crate::SCREEN_HEIGHT;
 use tcod::colors::WHITE;
 use tcod::console::Offscreen;
+use tcod::map::Map as FovMap;
 use tcod::{BackgroundFlag, Color, Console, TextAlignment};
 
 pub const BAR_WIDTH: i32 = 20;
@@ -40,3 +44,31 @@ pub fn render_bar(
         &format!("{}: {}/{}", name, value, maximum),
     );
 }
+
+// Draw every entity the player can see, or remembers seeing if it stays visible
+pub fn draw_entities(con: &mut Offscreen, fov: &FovMap, map: &Map, world: &World) {
+    let mut to_draw: Vec<_> = world
+        .renderables
+        .iter()
+        .filter_map(|(id, renderable)| {
+            let position = world.positions.get(id)?;
+            let (x, y) = (position.x, position.y);
+            // Telepathy reveals every creature with a mind, even through walls
+            let sensed = world.has_status(PLAYER, StatusKind::Telepathic) && world.ais.contains(id);
+            let shown = fov.is_in_fov(x, y)
+                || (renderable.always_visible && map[x as usize][y as usize].explored)
+                || sensed;
+            if shown {
+                Some((id, x, y, renderable))
+            } else {
+                None
+            }
+        })
+        .collect();
+    // Sort so that entities that don't block come first
+    to_draw.sort_by_key(|&(id, ..)| world.blockers.contains(id));
+    for (_, x, y, renderable) in to_draw {
+        con.set_default_foreground(renderable.color);
+        con.put_char(x, y, renderable.glyph, BackgroundFlag::None);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::entity::{EntityId, World};
+use crate::game::Game;
+use serde::{Deserialize, Serialize};
+use tcod::colors::*;
+use tcod::Color;
+
+pub const START_NUTRITION: i32 = 1000;
+pub const MAX_NUTRITION: i32 = 2000;
+const HUNGRY_NUTRITION: i32 = 300;
+const WEAK_NUTRITION: i32 = 150;
+const FAINTING_NUTRITION: i32 = 50;
+
+// Attack lost from being weak with hunger or worse
+pub const WEAK_POWER_PENALTY: i32 = 1;
+// Chance each turn for a fainting creature to pass out and lose its turn
+pub const FAINT_CHANCE: f32 = 0.2;
+const STARVE_DAMAGE: i32 = 1;
+
+// How much nutrition a corpse gives for each hit point its owner had
+pub const CORPSE_NUTRITION_PER_HP: i32 = 10;
+
+// How well fed a creature is. Goes down by one every turn
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Hunger {
+    pub nutrition: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum HungerState {
+    Fed,
+    Hungry,
+    Weak,
+    Fainting,
+    Starving,
+}
+
+impl std::fmt::Display for HungerState {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            HungerState::Fed => write!(f, "fed"),
+            HungerState::Hungry => write!(f, "hungry"),
+            HungerState::Weak => write!(f, "weak"),
+            HungerState::Fainting => write!(f, "fainting"),
+            HungerState::Starving => write!(f, "starving"),
+        }
+    }
+}
+
+impl HungerState {
+    pub fn color(self) -> Color {
+        match self {
+            HungerState::Fed => WHITE,
+            HungerState::Hungry => YELLOW,
+            HungerState::Weak => ORANGE,
+            HungerState::Fainting | HungerState::Starving => RED,
+        }
+    }
+}
+
+impl Hunger {
+    pub fn state(&self) -> HungerState {
+        match self.nutrition {
+            n if n <= 0 => HungerState::Starving,
+            n if n <= FAINTING_NUTRITION => HungerState::Fainting,
+            n if n <= WEAK_NUTRITION => HungerState::Weak,
+            n if n <= HUNGRY_NUTRITION => HungerState::Hungry,
+            _ => HungerState::Fed,
+        }
+    }
+}
+
+impl World {
+    // Creatures that don't need to eat are always fed
+    pub fn hunger_state(&self, id: EntityId) -> HungerState {
+        self.hungers
+            .get(id)
+            .map_or(HungerState::Fed, |hunger| hunger.state())
+    }
+
+    // Use up a turn's worth of food, warning the player as they get hungrier.
+    // Returns the xp the creature yields if starving kills it
+    pub fn digest(&mut self, id: EntityId, game: &mut Game) -> Option<i32> {
+        let hunger = self.hungers.get_mut(id)?;
+        let before = hunger.state();
+        hunger.nutrition = (hunger.nutrition - 1).max(0);
+        let after = hunger.state();
+        if after != before {
+            let message = match after {
+                HungerState::Hungry => "You are getting hungry.",
+                HungerState::Weak => "You feel weak with hunger.",
+                HungerState::Fainting => "You are fainting from lack of food!",
+                HungerState::Starving => "You are starving to death!",
+                HungerState::Fed => unreachable!(),
+            };
+            game.messages.add(message, after.color());
+        }
+        if after == HungerState::Starving {
+            return self.lose_hp(id, STARVE_DAMAGE, game);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_follows_the_nutrition_thresholds() {
+        let state = |nutrition| Hunger { nutrition }.state();
+        assert_eq!(state(START_NUTRITION), HungerState::Fed);
+        assert_eq!(state(HUNGRY_NUTRITION + 1), HungerState::Fed);
+        assert_eq!(state(HUNGRY_NUTRITION), HungerState::Hungry);
+        assert_eq!(state(WEAK_NUTRITION), HungerState::Weak);
+        assert_eq!(state(FAINTING_NUTRITION), HungerState::Fainting);
+        assert_eq!(state(1), HungerState::Fainting);
+        assert_eq!(state(0), HungerState::Starving);
+    }
+
+    #[test]
+    fn starving_ignores_physical_resistance() {
+        let mut game = Game::empty();
+        let mut world = World::default();
+        let id = world.spawn_test_fighter();
+        world.fighters[id].resistances.physical = 100;
+        world.hungers.insert(id, Hunger { nutrition: 1 });
+        world.digest(id, &mut game);
+        assert_eq!(world.fighters[id].hp, 10 - STARVE_DAMAGE);
+    }
+}
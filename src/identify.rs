@@ -0,0 +1,183 @@
+use crate::entity::{EntityId, World};
+use crate::loot::ItemTemplate;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+const POTION_LOOKS: &[&str] = &[
+    "murky",
+    "bubbling",
+    "fizzy",
+    "smoky",
+    "cloudy",
+    "glowing",
+    "oily",
+    "speckled",
+    "milky",
+    "golden",
+    "inky",
+    "sparkling",
+];
+const LABEL_SYLLABLES: &[&str] = &[
+    "XY", "ZZY", "AB", "RA", "KA", "DAB", "FOO", "NEX", "LOR", "ITH", "UL", "OM", "VEG", "PRA",
+    "ZU", "MOK",
+];
+
+// What the player has learned about potions and scrolls this run. Until one is
+// identified, it goes by an appearance picked at the start of the run
+#[derive(Serialize, Deserialize)]
+pub struct Knowledge {
+    appearances: BTreeMap<String, String>,
+    identified: BTreeSet<String>,
+}
+
+impl Knowledge {
+    pub fn new(templates: &[ItemTemplate]) -> Self {
+        let mut rng = rand::thread_rng();
+        let mut looks = POTION_LOOKS.to_vec();
+        looks.shuffle(&mut rng);
+        let mut appearances = BTreeMap::new();
+        for template in templates {
+            if template.effect.is_potion() {
+                // Once the looks run out, any further potions are simply known
+                if let Some(look) = looks.pop() {
+                    appearances.insert(template.name.clone(), format!("{} potion", look));
+                }
+            } else if template.effect.is_scroll() && template.charges.is_none() {
+                let label = loop {
+                    let label = random_label(&mut rng);
+                    if !appearances.values().any(|a| *a == label) {
+                        break label;
+                    }
+                };
+                appearances.insert(template.name.clone(), label);
+            }
+        }
+        Knowledge {
+            appearances,
+            identified: BTreeSet::new(),
+        }
+    }
+
+    pub fn is_known(&self, name: &str) -> bool {
+        !self.appearances.contains_key(name) || self.identified.contains(name)
+    }
+
+    // Learn what a kind of item is, returning whether it was unknown until now
+    pub fn identify(&mut self, name: &str) -> bool {
+        !self.is_known(name) && self.identified.insert(name.into())
+    }
+
+    // What a kind of item is called as far as the player can tell
+    pub fn name<'a>(&'a self, name: &'a str) -> &'a str {
+        match self.appearances.get(name) {
+            Some(appearance) if !self.identified.contains(name) => appearance,
+            _ => name,
+        }
+    }
+}
+
+fn random_label<R: Rng>(rng: &mut R) -> String {
+    let syllables = rng.gen_range(2, 4);
+    let label = (0..syllables)
+        .map(|_| *LABEL_SYLLABLES.choose(rng).unwrap())
+        .collect::<String>();
+    format!("scroll labeled {}", label)
+}
+
+// An item's name as the player knows it, with its enchantment once that's known
+pub fn item_name(id: EntityId, knowledge: &Knowledge, world: &World) -> String {
+    let name = knowledge.name(&world.names[id]);
+    match world.equipment.get(id) {
+        Some(equipment) if equipment.known && equipment.enchantment != 0 => {
+            format!("{:+} {}", equipment.enchantment, name)
+        }
+        _ => name.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(name: &str, effect: &str, charges: Option<i32>) -> ItemTemplate {
+        let json = format!(
+            r#"{{ "name": "{}", "glyph": "!", "color": {{ "r": 0, "g": 0, "b": 0 }},
+                 "effect": {}, "charges": {}, "spawn": [] }}"#,
+            name,
+            effect,
+            charges.map_or("null".into(), |c| c.to_string())
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn potions_and_scrolls_get_distinct_appearances() {
+        let templates = vec![
+            template("Healing Potion", r#"{ "Heal": { "amount": 40 } }"#, None),
+            template("Potion of Speed", r#"{ "Speed": { "turns": 20 } }"#, None),
+            template("Scroll of Identify", r#""Identify""#, None),
+            template("Scroll of Teleportation", r#""Teleport""#, None),
+        ];
+        let knowledge = Knowledge::new(&templates);
+        let names = templates
+            .iter()
+            .map(|t| knowledge.name(&t.name))
+            .collect::<Vec<_>>();
+        assert!(names[0].ends_with(" potion"));
+        assert!(names[1].ends_with(" potion"));
+        assert_ne!(names[0], names[1]);
+        assert!(names[2].starts_with("scroll labeled "));
+        assert!(names[3].starts_with("scroll labeled "));
+        assert_ne!(names[2], names[3]);
+        assert!(templates.iter().all(|t| !knowledge.is_known(&t.name)));
+    }
+
+    #[test]
+    fn wands_and_equipment_are_always_known() {
+        let templates = vec![
+            template(
+                "Wand of Lightning",
+                r#"{ "Lightning": { "damage": 30, "range": 5 } }"#,
+                Some(5),
+            ),
+            template("Sword", r#""Equip""#, None),
+        ];
+        let knowledge = Knowledge::new(&templates);
+        assert!(knowledge.is_known("Wand of Lightning"));
+        assert_eq!(knowledge.name("Sword"), "Sword");
+    }
+
+    #[test]
+    fn potions_past_the_last_look_are_known() {
+        let templates = (0..POTION_LOOKS.len() + 1)
+            .map(|i| {
+                template(
+                    &format!("Potion {}", i),
+                    r#"{ "Heal": { "amount": 1 } }"#,
+                    None,
+                )
+            })
+            .collect::<Vec<_>>();
+        let knowledge = Knowledge::new(&templates);
+        let known = templates
+            .iter()
+            .filter(|t| knowledge.is_known(&t.name))
+            .count();
+        assert_eq!(known, 1);
+    }
+
+    #[test]
+    fn identifying_reveals_the_real_name_once() {
+        let templates = vec![template(
+            "Healing Potion",
+            r#"{ "Heal": { "amount": 40 } }"#,
+            None,
+        )];
+        let mut knowledge = Knowledge::new(&templates);
+        assert!(knowledge.identify("Healing Potion"));
+        assert!(!knowledge.identify("Healing Potion"));
+        assert_eq!(knowledge.name("Healing Potion"), "Healing Potion");
+    }
+}
@@ -1,31 +1,175 @@
-use crate::ai::Ai;
-use crate::equipment::get_equipped_in_slot;
-use crate::game::Game;
-use crate::map::PLAYER;
+use crate::ai::{provokes, Ai, Awareness, Faction, HUNT_TURNS, RAGE_TURNS};
+use crate::container::open_container;
+use crate::damage::DamageType;
+use crate::entity::{EntityId, World, PLAYER};
+use crate::equipment::claim_slot;
+use crate::game::{Game, IMPACT_NOISE, SPELL_NOISE};
+use crate::hunger::MAX_NUTRITION;
+use crate::identify::{item_name, Knowledge};
+use crate::map::{inventory_menu, MAP_HEIGHT, MAP_WIDTH};
 use crate::object::*;
+use crate::ranged::trace_projectile;
+use crate::shop::check_theft;
+use crate::status::StatusKind;
 use crate::Tcod;
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
 use serde::{Deserialize, Serialize};
 use tcod::colors::*;
 
-const HEAL_AMOUNT: i32 = 40;
+const THROW_RANGE: i32 = 6;
+// Added to a weapon's power bonus when it is thrown
+const THROWN_WEAPON_DAMAGE: i32 = 2;
+const THROWN_AMMO_DAMAGE: i32 = 1;
+const SHATTER_RADIUS: i32 = 1;
+const TELEPORT_TRIES: usize = 100;
 
-const LIGHTNING_DAMAGE: i32 = 40;
-const LIGHTNING_RANGE: i32 = 5;
+// What happens when an item is used, with the strength of the effect
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Item {
+    Heal { amount: i32 },
+    Lightning { damage: i32, range: i32 },
+    Confuse { range: i32, turns: i32 },
+    Fireball { damage: i32, radius: i32 },
+    Charm { range: i32 },
+    Identify,
+    Enchant,
+    RemoveCurse,
+    Recharge,
+    Teleport,
+    MagicMapping,
+    Fear { radius: i32, turns: i32 },
+    SummonMonster,
+    Strength { amount: i32 },
+    Speed { turns: i32 },
+    Invisibility { turns: i32 },
+    Telepathy { turns: i32 },
+    Poison { turns: i32 },
+    Food { nutrition: i32 },
+    Gold,
+    // Opens up to move things in and out of it
+    Bag,
+    Equip,
+    Ammunition,
+}
 
-const CONFUSE_RANGE: i32 = 8;
-const CONFUSE_NUM_TURNS: i32 = 10;
+impl Item {
+    pub fn is_potion(self) -> bool {
+        use Item::*;
+        matches!(
+            self,
+            Heal { .. }
+                | Strength { .. }
+                | Speed { .. }
+                | Invisibility { .. }
+                | Telepathy { .. }
+                | Poison { .. }
+        )
+    }
 
-const FIREBALL_RADIUS: i32 = 3;
-const FIREBALL_DAMAGE: i32 = 25;
+    pub fn is_scroll(self) -> bool {
+        use Item::*;
+        matches!(
+            self,
+            Lightning { .. }
+                | Confuse { .. }
+                | Fireball { .. }
+                | Charm { .. }
+                | Identify
+                | Enchant
+                | RemoveCurse
+                | Recharge
+                | Teleport
+                | MagicMapping
+                | Fear { .. }
+                | SummonMonster
+        )
+    }
 
-#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
-pub enum Item {
-    Heal,
-    Lightning,
-    Confuse,
-    Fireball,
-    Sword,
-    Shield,
+    // Potions, scrolls and ammunition pile up; equipment and bags are kept apart
+    pub fn stacks(self) -> bool {
+        !matches!(self, Item::Equip | Item::Bag)
+    }
+}
+
+// How many identical items one entity stands for
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Stack {
+    pub count: i32,
+}
+
+// Wands and staffs use up a charge instead of themselves
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Charges {
+    pub current: i32,
+    pub max: i32,
+}
+
+fn stacks_with(id: EntityId, other: EntityId, world: &World) -> bool {
+    id != other
+        && world.stacks.contains(id)
+        && world.stacks.contains(other)
+        && world.names.get(id) == world.names.get(other)
+        && world.items.get(id) == world.items.get(other)
+        && world.ammo.get(id) == world.ammo.get(other)
+        && world.wares.get(id) == world.wares.get(other)
+}
+
+pub fn count(id: EntityId, world: &World) -> i32 {
+    world.stacks.get(id).map_or(1, |stack| stack.count)
+}
+
+pub fn plural(name: &str) -> String {
+    // "Scroll of Fireball" becomes "Scrolls of Fireball"
+    let (noun, rest) = match name.find(" of ").or_else(|| name.find(" labeled ")) {
+        Some(index) => name.split_at(index),
+        None => (name, ""),
+    };
+    if noun.ends_with('s') {
+        name.into()
+    } else {
+        format!("{}s{}", noun, rest)
+    }
+}
+
+// An item's name along with how many there are, like "3 Healing Potions"
+pub fn describe(id: EntityId, knowledge: &Knowledge, world: &World) -> String {
+    let name = item_name(id, knowledge, world);
+    match count(id, world) {
+        1 => name,
+        count => format!("{} {}", count, plural(&name)),
+    }
+}
+
+// Take a single item out of someone's inventory, splitting it off its stack if
+// there are more. The item is left without a place, like `take_from_inventory`
+pub fn take_one(
+    owner: EntityId,
+    item_id: EntityId,
+    game: &mut Game,
+    world: &mut World,
+) -> EntityId {
+    if count(item_id, world) > 1 {
+        let single = world.duplicate(item_id);
+        world.stacks[single].count = 1;
+        world.stacks[item_id].count -= 1;
+        single
+    } else {
+        take_from_inventory(owner, item_id, game, world);
+        item_id
+    }
+}
+
+// Put an item on the map, adding it to any pile of the same kind already there
+pub fn place_item(item_id: EntityId, x: i32, y: i32, world: &mut World) {
+    let pile = world.at(x, y).find(|&id| stacks_with(id, item_id, world));
+    match pile {
+        Some(pile) => {
+            world.stacks[pile].count += count(item_id, world);
+            world.despawn(item_id);
+        }
+        None => world.set_pos(item_id, x, y),
+    }
 }
 
 enum UseResult {
@@ -34,47 +178,34 @@ enum UseResult {
     Cancelled,
 }
 
-fn cast_heal(
-    _inventory_id: usize,
-    _tcod: &mut Tcod,
-    game: &mut Game,
-    objects: &mut [Object],
-) -> UseResult {
+fn cast_heal(amount: i32, game: &mut Game, world: &mut World) -> UseResult {
     // Heal the player
-    let player = &mut objects[PLAYER];
-    if let Some(fighter) = player.fighter {
-        if fighter.hp == player.max_hp(game) {
+    if let Some(fighter) = world.fighters.get(PLAYER) {
+        if fighter.hp == world.max_hp(PLAYER) {
             game.messages.add("You are already at full health.", RED);
             return UseResult::Cancelled;
         }
         game.messages
             .add("Your wounds start to feel better!", LIGHT_VIOLET);
-        objects[PLAYER].heal(HEAL_AMOUNT, game);
+        world.heal(PLAYER, amount);
         return UseResult::UsedUp;
     }
     UseResult::Cancelled
 }
 
 fn cast_lightning(
-    _inventory_id: usize,
+    damage: i32,
+    range: i32,
     tcod: &mut Tcod,
     game: &mut Game,
-    objects: &mut [Object],
+    world: &mut World,
 ) -> UseResult {
     // Find closest enemy (inside a maximum range and damage it)
-    let monster_id = closest_monster(tcod, objects, LIGHTNING_RANGE);
+    let monster_id = closest_monster(tcod, world, range);
     if let Some(monster_id) = monster_id {
         // Zap it!
-        game.messages.add(
-            format!(
-                "A lightning bolt strikes the {} with a loud thunder! \
-                 The damage is {} hit poiints.",
-                objects[monster_id].name, LIGHTNING_DAMAGE
-            ),
-            LIGHT_BLUE,
-        );
-        if let Some(xp) = objects[monster_id].take_damage(LIGHTNING_DAMAGE, game) {
-            objects[PLAYER].fighter.as_mut().unwrap().xp += xp;
+        if let Some(xp) = lightning_strike(monster_id, damage, game, world) {
+            world.fighters[PLAYER].xp += xp;
         }
         UseResult::UsedUp
     } else {
@@ -86,27 +217,16 @@ fn cast_lightning(
 }
 
 fn cast_confuse(
-    _inventory_id: usize,
+    range: i32,
+    turns: i32,
     tcod: &mut Tcod,
     game: &mut Game,
-    objects: &mut [Object],
+    world: &mut World,
 ) -> UseResult {
     // Find closest enemy in-range and confuse it
-    let monster_id = target_monster(tcod, game, objects, Some(CONFUSE_RANGE as f32));
+    let monster_id = target_monster(tcod, game, world, Some(range as f32));
     if let Some(monster_id) = monster_id {
-        let old_ai = objects[monster_id].ai.take().unwrap_or(Ai::Basic);
-        // Replace the monster's AI with a "confused" one
-        objects[monster_id].ai = Some(Ai::Confused {
-            previous_ai: Box::new(old_ai),
-            num_turns: CONFUSE_NUM_TURNS,
-        });
-        game.messages.add(
-            format!(
-                "The eyes of {} look vacant, as he starts to stumble around!",
-                objects[monster_id].name,
-            ),
-            LIGHT_GREEN,
-        );
+        confuse(monster_id, turns, game, world);
         UseResult::UsedUp
     } else {
         // No enemy found within max range
@@ -117,39 +237,111 @@ fn cast_confuse(
 }
 
 fn cast_fireball(
-    _inventory_id: usize,
+    damage: i32,
+    radius: i32,
     tcod: &mut Tcod,
     game: &mut Game,
-    objects: &mut [Object],
+    world: &mut World,
 ) -> UseResult {
     // Ask the player for a target tile to throw a fireball at
     game.messages.add(
         "Left-click a target tile for the fireball, or right-click to cancel",
         LIGHT_CYAN,
     );
-    let (x, y) = match target_tile(tcod, game, objects, None) {
+    let (x, y) = match target_tile(tcod, game, world, None) {
         Some(tile_pos) => tile_pos,
         None => return UseResult::Cancelled,
     };
+    let xp_to_gain = fireball_blast(x, y, radius, damage, PLAYER, game, world);
+    world.fighters[PLAYER].xp += xp_to_gain;
+
+    UseResult::UsedUp
+}
+
+// Strike a creature with lightning, returning the xp it yields if it dies
+pub fn lightning_strike(
+    target_id: EntityId,
+    damage: i32,
+    game: &mut Game,
+    world: &mut World,
+) -> Option<i32> {
+    let dealt = world.resisted_damage(target_id, damage, DamageType::Lightning);
+    game.messages.add(
+        format!(
+            "A lightning bolt strikes the {} with a loud thunder! \
+             The damage is {} hit poiints.",
+            world.names[target_id], dealt
+        ),
+        LIGHT_BLUE,
+    );
+    let (x, y) = world.pos(target_id);
+    game.make_noise(x, y, SPELL_NOISE);
+    world.take_damage(target_id, damage, DamageType::Lightning, game)
+}
+
+pub fn confuse(target_id: EntityId, turns: i32, game: &mut Game, world: &mut World) {
+    if target_id == PLAYER {
+        // The player has no AI to replace, so confusion is a status instead
+        world.add_status(PLAYER, StatusKind::Confused, turns);
+        game.messages.add(
+            "Your head spins, and you start to stumble around!",
+            LIGHT_GREEN,
+        );
+        return;
+    }
+    let old_ai = world.ais.remove(target_id).unwrap_or(Ai::Basic);
+    // Replace the monster's AI with a "confused" one
+    world.ais.insert(
+        target_id,
+        Ai::Confused {
+            previous_ai: Box::new(old_ai),
+            num_turns: turns,
+        },
+    );
+    game.messages.add(
+        format!(
+            "The eyes of {} look vacant, as he starts to stumble around!",
+            world.names[target_id],
+        ),
+        LIGHT_GREEN,
+    );
+}
+
+// Burn everything around (x, y), returning the xp yielded by slain monsters
+pub fn fireball_blast(
+    x: i32,
+    y: i32,
+    radius: i32,
+    damage: i32,
+    caster_id: EntityId,
+    game: &mut Game,
+    world: &mut World,
+) -> i32 {
+    let caster_faction = world.factions.get(caster_id).copied();
     game.messages.add(
         format!(
             "The fireball explodes, burning everything within {} tiles!",
-            FIREBALL_RADIUS,
+            radius,
         ),
         ORANGE,
     );
+    game.make_noise(x, y, SPELL_NOISE);
 
     let mut xp_to_gain = 0;
-    for (id, obj) in objects.iter_mut().enumerate() {
-        if obj.distance(x, y) <= FIREBALL_RADIUS as f32 && obj.fighter.is_some() {
+    for id in world.fighters.ids() {
+        if world.distance(id, x, y) <= radius as f32 {
+            let dealt = world.resisted_damage(id, damage, DamageType::Fire);
             game.messages.add(
                 format!(
                     "The {} gets burned for {} hit points.",
-                    obj.name, FIREBALL_DAMAGE
+                    world.names[id], dealt
                 ),
                 ORANGE,
             );
-            if let Some(xp) = obj.take_damage(FIREBALL_DAMAGE, game) {
+            if provokes(caster_faction, world.factions.get(id).copied()) {
+                world.add_status(id, StatusKind::Enraged, RAGE_TURNS);
+            }
+            if let Some(xp) = world.take_damage(id, damage, DamageType::Fire, game) {
                 if id != PLAYER {
                     // Don't reward the player for burning themself!
                     xp_to_gain += xp;
@@ -157,49 +349,401 @@ fn cast_fireball(
             }
         }
     }
-    objects[PLAYER].fighter.as_mut().unwrap().xp += xp_to_gain;
+    xp_to_gain
+}
+
+fn cast_charm(range: i32, tcod: &mut Tcod, game: &mut Game, world: &mut World) -> UseResult {
+    // Ask the player for a monster to win over
+    game.messages.add(
+        "Left-click a monster to charm it, or right-click to cancel",
+        LIGHT_CYAN,
+    );
+    let monster_id = match target_monster(tcod, game, world, Some(range as f32)) {
+        Some(monster_id) => monster_id,
+        None => return UseResult::Cancelled,
+    };
+    if world.factions.get(monster_id) == Some(&Faction::Player) {
+        game.messages.add(
+            format!("The {} is already your friend.", world.names[monster_id]),
+            WHITE,
+        );
+        return UseResult::Cancelled;
+    }
+    world.factions.insert(monster_id, Faction::Player);
+    world.ais.insert(monster_id, Ai::Ally);
+    world.awareness.remove(monster_id);
+    if let Some(statuses) = world.statuses.get_mut(monster_id) {
+        statuses.retain(|s| s.kind != StatusKind::Enraged);
+    }
+    game.messages.add(
+        format!(
+            "The {} looks at you adoringly. It is now your ally!",
+            world.names[monster_id]
+        ),
+        LIGHT_PINK,
+    );
+    UseResult::UsedUp
+}
+
+// Ask the player to pick one of the candidates. The scroll is already read, so
+// keep asking until a choice is made
+fn choose_item(
+    candidates: &[EntityId],
+    header: &str,
+    tcod: &mut Tcod,
+    game: &Game,
+    world: &World,
+) -> EntityId {
+    loop {
+        if let Some(chosen) =
+            inventory_menu(candidates, &game.knowledge, world, header, &mut tcod.root)
+        {
+            return chosen;
+        }
+    }
+}
+
+fn cast_identify(item_id: EntityId, tcod: &mut Tcod, game: &mut Game, world: &World) -> UseResult {
+    // The scroll being read is identified by reading it anyway
+    let unknown = world.inventories[PLAYER]
+        .iter()
+        .cloned()
+        .filter(|&id| world.names[id] != world.names[item_id])
+        .filter(|&id| !game.knowledge.is_known(&world.names[id]))
+        .collect::<Vec<_>>();
+    if unknown.is_empty() {
+        game.messages
+            .add("You have nothing left to identify.", WHITE);
+        return UseResult::UsedUp;
+    }
+    let chosen = choose_item(&unknown, "Choose an item to identify.\n", tcod, game, world);
+    game.knowledge.identify(&world.names[chosen]);
+    game.messages
+        .add(format!("That is a {}.", world.names[chosen]), LIGHT_CYAN);
+    UseResult::UsedUp
+}
+
+fn cast_enchant(tcod: &mut Tcod, game: &mut Game, world: &mut World) -> UseResult {
+    let equipment = world.inventories[PLAYER]
+        .iter()
+        .cloned()
+        .filter(|&id| world.equipment.contains(id))
+        .collect::<Vec<_>>();
+    if equipment.is_empty() {
+        game.messages
+            .add("Your hands tingle, but nothing else happens.", WHITE);
+        return UseResult::UsedUp;
+    }
+    let chosen = choose_item(
+        &equipment,
+        "Choose an item to enchant.\n",
+        tcod,
+        game,
+        world,
+    );
+    let enchanted = &mut world.equipment[chosen];
+    enchanted.enchantment += 1;
+    // Raising a cursed item back to zero breaks the curse
+    if enchanted.cursed && enchanted.enchantment >= 0 {
+        enchanted.cursed = false;
+    }
+    game.messages.add(
+        format!("Your {} glows blue for a moment.", world.names[chosen]),
+        LIGHT_BLUE,
+    );
+    UseResult::UsedUp
+}
+
+fn cast_remove_curse(game: &mut Game, world: &mut World) -> UseResult {
+    // Lifts the curse from everything the player is wearing
+    let inventory = world.inventories[PLAYER].clone();
+    for id in inventory {
+        if let Some(equipment) = world.equipment.get_mut(id).filter(|e| e.equipped) {
+            equipment.cursed = false;
+        }
+    }
+    game.messages
+        .add("You feel like someone is watching over you.", LIGHT_VIOLET);
+    UseResult::UsedUp
+}
+
+fn cast_recharge(tcod: &mut Tcod, game: &mut Game, world: &mut World) -> UseResult {
+    let chargeable = world.inventories[PLAYER]
+        .iter()
+        .cloned()
+        .filter(|&id| world.charges.contains(id))
+        .collect::<Vec<_>>();
+    if chargeable.is_empty() {
+        game.messages
+            .add("You feel a surge of energy, but it fades away.", WHITE);
+        return UseResult::UsedUp;
+    }
+    let chosen = choose_item(
+        &chargeable,
+        "Choose an item to recharge.\n",
+        tcod,
+        game,
+        world,
+    );
+    let charges = &mut world.charges[chosen];
+    charges.current = charges.max;
+    game.messages.add(
+        format!("Your {} hums with power.", world.names[chosen]),
+        LIGHT_BLUE,
+    );
+    UseResult::UsedUp
+}
+
+fn cast_teleport(game: &mut Game, world: &mut World) -> UseResult {
+    // Land on a random free tile somewhere on the level
+    for _ in 0..TELEPORT_TRIES {
+        let x = rand::thread_rng().gen_range(0, MAP_WIDTH);
+        let y = rand::thread_rng().gen_range(0, MAP_HEIGHT);
+        if !is_blocked(x, y, &game.map, world) {
+            world.set_pos(PLAYER, x, y);
+            game.messages
+                .add("You feel yourself yanked away!", LIGHT_VIOLET);
+            return UseResult::UsedUp;
+        }
+    }
+    game.messages
+        .add("You feel a brief tug, but stay where you are.", WHITE);
+    UseResult::UsedUp
+}
+
+fn cast_magic_mapping(game: &mut Game) -> UseResult {
+    // Reveal every floor tile along with the walls around it
+    let map = &mut game.map;
+    for x in 0..MAP_WIDTH {
+        for y in 0..MAP_HEIGHT {
+            let next_to_floor = (-1..=1).any(|dx| {
+                (-1..=1).any(|dy| {
+                    let (nx, ny) = (x + dx, y + dy);
+                    nx >= 0
+                        && ny >= 0
+                        && nx < MAP_WIDTH
+                        && ny < MAP_HEIGHT
+                        && !map[nx as usize][ny as usize].blocked
+                })
+            });
+            if next_to_floor {
+                map[x as usize][y as usize].explored = true;
+            }
+        }
+    }
+    game.messages.add("A map forms in your mind!", LIGHT_CYAN);
+    UseResult::UsedUp
+}
+
+fn cast_fear(radius: i32, turns: i32, game: &mut Game, world: &mut World) -> UseResult {
+    // Every hostile monster close enough runs from the player
+    let mut scared = 0;
+    for id in world.ais.ids() {
+        if world.is_hostile(PLAYER, id) && world.distance_to(PLAYER, id) <= radius as f32 {
+            world.add_status(id, StatusKind::Afraid, turns);
+            scared += 1;
+        }
+    }
+    if scared > 0 {
+        game.messages
+            .add("A terrible wail fills the air!", LIGHT_VIOLET);
+    } else {
+        game.messages.add(
+            "You hear a distant wail, but nothing is close enough to hear it.",
+            WHITE,
+        );
+    }
+    UseResult::UsedUp
+}
 
+fn cast_summon_monster(game: &mut Game, world: &mut World) -> UseResult {
+    // Calls up a hostile monster of the kind found at this depth
+    let level = game.dungeon_level;
+    let chances = game
+        .templates
+        .monsters
+        .iter()
+        .map(|monster| monster.spawn_weight(level))
+        .collect::<Vec<_>>();
+    let (player_x, player_y) = world.pos(PLAYER);
+    let spot = free_spot_near(player_x, player_y, &game.map, world);
+    let (choice, (x, y)) = match (WeightedIndex::new(&chances).ok(), spot) {
+        (Some(choice), Some(spot)) => (choice, spot),
+        _ => {
+            game.messages.add("The air shimmers for a moment.", WHITE);
+            return UseResult::UsedUp;
+        }
+    };
+    let template = &game.templates.monsters[choice.sample(&mut rand::thread_rng())];
+    let monster_id = template.spawn(world, x, y, &game.templates.items);
+    world.awareness.insert(
+        monster_id,
+        Awareness::Hunting {
+            x: player_x,
+            y: player_y,
+            turns_left: HUNT_TURNS,
+        },
+    );
+    game.messages.add(
+        format!("A {} appears out of thin air!", world.names[monster_id]),
+        RED,
+    );
     UseResult::UsedUp
 }
 
-fn toggle_equipment(
-    inventory_id: usize,
-    _tcod: &mut Tcod,
+fn cast_strength(amount: i32, game: &mut Game, world: &mut World) -> UseResult {
+    world.fighters[PLAYER].base_power += amount;
+    game.messages.add("You feel stronger!", LIGHT_VIOLET);
+    UseResult::UsedUp
+}
+
+// Potions that put a temporary effect on the player
+fn cast_status(
+    kind: StatusKind,
+    turns: i32,
+    message: &str,
     game: &mut Game,
-    _objects: &mut [Object],
+    world: &mut World,
 ) -> UseResult {
-    let equipment = match game.inventory[inventory_id].equipment {
-        Some(equipment) => equipment,
+    world.add_status(PLAYER, kind, turns);
+    game.messages.add(message, LIGHT_VIOLET);
+    UseResult::UsedUp
+}
+
+fn cast_eat(nutrition: i32, item_id: EntityId, game: &mut Game, world: &mut World) -> UseResult {
+    let hunger = match world.hungers.get_mut(PLAYER) {
+        Some(hunger) => hunger,
+        None => return UseResult::Cancelled,
+    };
+    if hunger.nutrition + nutrition > MAX_NUTRITION {
+        game.messages.add("You are too full to eat that.", RED);
+        return UseResult::Cancelled;
+    }
+    hunger.nutrition += nutrition;
+    game.messages.add(
+        format!("You eat the {}. That hit the spot!", world.names[item_id]),
+        LIGHT_GREEN,
+    );
+    UseResult::UsedUp
+}
+
+fn toggle_equipment(item_id: EntityId, game: &mut Game, world: &mut World) -> UseResult {
+    let equipment = match world.equipment.get(item_id) {
+        Some(&equipment) => equipment,
         None => return UseResult::Cancelled,
     };
     if equipment.equipped {
-        game.inventory[inventory_id].dequip(&mut game.messages);
+        if world.can_remove(item_id, &mut game.messages) {
+            world.dequip(item_id, &mut game.messages);
+        }
     } else {
-        // If the slot is already being used, dequip whatever is there first
-        if let Some(current) = get_equipped_in_slot(equipment.slot, &game.inventory) {
-            game.inventory[current].dequip(&mut game.messages);
+        // If the slots are already being used, dequip whatever is there first
+        let in_the_way = claim_slot(PLAYER, item_id, world);
+        if !in_the_way
+            .iter()
+            .all(|&current| world.can_remove(current, &mut game.messages))
+        {
+            return UseResult::UsedAndKept;
+        }
+        for current in in_the_way {
+            world.dequip(current, &mut game.messages);
         }
-        game.inventory[inventory_id].equip(&mut game.messages);
+        world.equip(item_id, &mut game.messages);
     }
     UseResult::UsedAndKept
 }
 
-pub fn use_item(inventory_id: usize, tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) {
+fn ready_ammo(item_id: EntityId, game: &mut Game, world: &World) -> UseResult {
+    // Ammunition is shot from a ranged weapon rather than used directly
+    game.messages.add(
+        format!(
+            "Equip a ranged weapon and press 'f' to fire your {}.",
+            world.names[item_id]
+        ),
+        WHITE,
+    );
+    UseResult::UsedAndKept
+}
+
+pub fn use_item(item_id: EntityId, tcod: &mut Tcod, game: &mut Game, world: &mut World) {
     use Item::*;
-    // Just call the "use_function" if it is defined
-    if let Some(item) = game.inventory[inventory_id].item {
-        let on_use = match item {
-            Heal => cast_heal,
-            Lightning => cast_lightning,
-            Confuse => cast_confuse,
-            Fireball => cast_fireball,
-            Sword => toggle_equipment,
-            Shield => toggle_equipment,
+    // Just apply the item's effect if it has one
+    if let Some(&item) = world.items.get(item_id) {
+        if world.charges.get(item_id).is_some_and(|c| c.current <= 0) {
+            game.messages.add(
+                format!("The {} has no charges left.", world.names[item_id]),
+                RED,
+            );
+            return;
+        }
+        let result = match item {
+            Heal { amount } => cast_heal(amount, game, world),
+            Lightning { damage, range } => cast_lightning(damage, range, tcod, game, world),
+            Confuse { range, turns } => cast_confuse(range, turns, tcod, game, world),
+            Fireball { damage, radius } => cast_fireball(damage, radius, tcod, game, world),
+            Charm { range } => cast_charm(range, tcod, game, world),
+            Identify => cast_identify(item_id, tcod, game, world),
+            Enchant => cast_enchant(tcod, game, world),
+            Recharge => cast_recharge(tcod, game, world),
+            Teleport => cast_teleport(game, world),
+            MagicMapping => cast_magic_mapping(game),
+            Fear { radius, turns } => cast_fear(radius, turns, game, world),
+            SummonMonster => cast_summon_monster(game, world),
+            Strength { amount } => cast_strength(amount, game, world),
+            Speed { turns } => cast_status(
+                StatusKind::Hasted,
+                turns,
+                "You feel yourself speed up!",
+                game,
+                world,
+            ),
+            Invisibility { turns } => cast_status(
+                StatusKind::Invisible,
+                turns,
+                "Your body fades from sight!",
+                game,
+                world,
+            ),
+            Telepathy { turns } => cast_status(
+                StatusKind::Telepathic,
+                turns,
+                "Your mind reaches out to the creatures around you.",
+                game,
+                world,
+            ),
+            Food { nutrition } => cast_eat(nutrition, item_id, game, world),
+            Poison { turns } => cast_status(
+                StatusKind::Poisoned,
+                turns,
+                "You feel very sick!",
+                game,
+                world,
+            ),
+            RemoveCurse => cast_remove_curse(game, world),
+            // Gold goes straight into the purse, so it's never in the inventory
+            Gold => UseResult::UsedAndKept,
+            Bag => {
+                open_container(item_id, tcod, game, world);
+                UseResult::UsedAndKept
+            }
+            Equip => toggle_equipment(item_id, game, world),
+            Ammunition => ready_ammo(item_id, game, world),
         };
-        match on_use(inventory_id, tcod, game, objects) {
+        match result {
             UseResult::UsedUp => {
-                // Destroy after use, unless it was cancelled for some reason
-                game.inventory.remove(inventory_id);
+                // Using up an item shows what it was
+                if game.knowledge.identify(&world.names[item_id]) {
+                    game.messages
+                        .add(format!("It was a {}.", world.names[item_id]), LIGHT_CYAN);
+                }
+                // Destroy after use, unless it was cancelled for some reason.
+                // Items with charges only lose one
+                if let Some(charges) = world.charges.get_mut(item_id) {
+                    charges.current -= 1;
+                } else {
+                    let used = take_one(PLAYER, item_id, game, world);
+                    world.despawn(used);
+                }
             }
             // Do nothing
             UseResult::UsedAndKept => {}
@@ -209,67 +753,246 @@ pub fn use_item(inventory_id: usize, tcod: &mut Tcod, game: &mut Game, objects:
         }
     } else {
         game.messages.add(
-            format!("The {} cannot be used.", game.inventory[inventory_id].name),
+            format!(
+                "The {} cannot be used.",
+                item_name(item_id, &game.knowledge, world)
+            ),
             WHITE,
         );
     }
 }
 
-pub fn pick_item_up(object_id: usize, game: &mut Game, objects: &mut Vec<Object>) {
-    if game.inventory.len() >= 26 {
+// Remove an item from someone's inventory, taking it off first if it is worn.
+// The item is left without a place until it's put somewhere else
+pub fn take_from_inventory(owner: EntityId, item_id: EntityId, game: &mut Game, world: &mut World) {
+    if let Some(inventory) = world.inventories.get_mut(owner) {
+        inventory.retain(|&id| id != item_id);
+    }
+    if world.equipment.contains(item_id) {
+        world.dequip(item_id, &mut game.messages);
+    }
+}
+
+pub fn drop_item(item_id: EntityId, game: &mut Game, world: &mut World) {
+    if !world.can_remove(item_id, &mut game.messages) {
+        return;
+    }
+    let item_id = take_one(PLAYER, item_id, game, world);
+    let name = item_name(item_id, &game.knowledge, world);
+    game.messages
+        .add(format!("You dropped a {}.", name), YELLOW);
+    let (x, y) = world.pos(PLAYER);
+    place_item(item_id, x, y, world);
+}
+
+fn shatter_potion(item: Item, x: i32, y: i32, game: &mut Game, world: &mut World) {
+    match item {
+        Item::Heal { amount } => {
+            // The healing spills over everything nearby, friend or foe
+            for id in world.fighters.ids() {
+                if world.distance(id, x, y) <= SHATTER_RADIUS as f32 {
+                    world.heal(id, amount);
+                    game.messages.add(
+                        format!("The {} looks healthier!", world.names[id]),
+                        LIGHT_VIOLET,
+                    );
+                }
+            }
+        }
+        Item::Poison { turns } => {
+            for id in world.fighters.ids() {
+                if world.distance(id, x, y) <= SHATTER_RADIUS as f32 {
+                    world.add_status(id, StatusKind::Poisoned, turns);
+                    game.messages.add(
+                        format!("The {} chokes on the fumes!", world.names[id]),
+                        LIGHT_GREEN,
+                    );
+                }
+            }
+        }
+        // The rest only work when drunk
+        _ => game
+            .messages
+            .add("The potion evaporates harmlessly.", LIGHT_GREY),
+    }
+}
+
+pub fn throw_item(item_id: EntityId, tcod: &mut Tcod, game: &mut Game, world: &mut World) -> bool {
+    if !world.can_remove(item_id, &mut game.messages) {
+        return false;
+    }
+    game.messages.add(
+        format!(
+            "Left-click a target tile to throw the {} at, or right-click to cancel",
+            item_name(item_id, &game.knowledge, world)
+        ),
+        LIGHT_CYAN,
+    );
+    let target = match target_tile(tcod, game, world, Some(THROW_RANGE as f32)) {
+        Some(tile_pos) => tile_pos,
+        None => return false,
+    };
+
+    // Only a single item is thrown out of a stack
+    let item_id = take_one(PLAYER, item_id, game, world);
+    let name = item_name(item_id, &game.knowledge, world);
+    game.messages.add(format!("You throw the {}.", name), WHITE);
+
+    let (hit, (x, y)) = trace_projectile(world.pos(PLAYER), target, &game.map, world);
+    game.make_noise(x, y, IMPACT_NOISE);
+
+    // Potions break on impact and splash their contents around
+    if let Some(&potion) = world.items.get(item_id).filter(|i| i.is_potion()) {
+        game.messages
+            .add(format!("The {} shatters!", name), LIGHT_BLUE);
+        // What it does to those nearby gives it away
+        game.knowledge.identify(&world.names[item_id]);
+        world.despawn(item_id);
+        shatter_potion(potion, x, y, game, world);
+        return true;
+    }
+
+    if let Some(target_id) = hit {
+        let damage = match (world.equipment.get(item_id), world.ammo.get(item_id)) {
+            (Some(equipment), _) if equipment.power() > 0 => {
+                equipment.power() + THROWN_WEAPON_DAMAGE
+            }
+            (_, Some(_)) => THROWN_AMMO_DAMAGE,
+            _ => 0,
+        } - world.defense(target_id);
+        let dealt = world.resisted_damage(target_id, damage, DamageType::Physical);
+        if dealt > 0 {
+            game.messages.add(
+                format!(
+                    "The {} hits the {} for {} hit points.",
+                    name, world.names[target_id], dealt
+                ),
+                WHITE,
+            );
+            if let Some(xp) = world.take_damage(target_id, damage, DamageType::Physical, game) {
+                world.fighters[PLAYER].xp += xp;
+            }
+        } else {
+            game.messages.add(
+                format!(
+                    "The {} bounces harmlessly off the {}.",
+                    name, world.names[target_id]
+                ),
+                WHITE,
+            );
+        }
+    }
+
+    // Whatever is left lands on the map
+    place_item(item_id, x, y, world);
+    true
+}
+
+// Put an item in someone's inventory, taking it off the map. It's added to a
+// stack of the same kind if there is one; returns the item as it's now carried
+pub fn add_to_inventory(owner: EntityId, item_id: EntityId, world: &mut World) -> EntityId {
+    world.positions.remove(item_id);
+    let stack = world.inventories[owner]
+        .iter()
+        .cloned()
+        .find(|&id| stacks_with(id, item_id, world));
+    if let Some(stack) = stack {
+        world.stacks[stack].count += count(item_id, world);
+        world.despawn(item_id);
+        return stack;
+    }
+    world.inventories[owner].push(item_id);
+    item_id
+}
+
+// Quietly put on a carried item if nothing else is worn in its slot
+pub fn equip_if_free(owner: EntityId, item_id: EntityId, world: &mut World) -> bool {
+    if !world.equipment.contains(item_id) || !claim_slot(owner, item_id, world).is_empty() {
+        return false;
+    }
+    world.equipment[item_id].equipped = true;
+    true
+}
+
+// Whether the player can take an item without going over the weight limit,
+// saying why not if they can't
+pub fn fits_in_inventory(item_id: EntityId, game: &mut Game, world: &World) -> bool {
+    // Moving things out of a bag the player carries doesn't change their load
+    let in_carried_bag = world.inventories[PLAYER]
+        .iter()
+        .any(|&bag| world.containers.contains(bag) && world.inventories[bag].contains(&item_id));
+    if !in_carried_bag && !world.can_carry(PLAYER, item_id) {
         game.messages.add(
             format!(
-                "Your inventory is full, cannot pick up {}",
-                objects[object_id].name,
+                "The {} is too heavy to carry with everything else.",
+                describe(item_id, &game.knowledge, world),
             ),
             RED,
         );
+        false
     } else {
-        let item = objects.swap_remove(object_id);
-        game.messages
-            .add(format!("You picked up a {}!", item.name), GREEN);
-        let index = game.inventory.len();
-        let slot = item.equipment.map(|e| e.slot);
-        game.inventory.push(item);
-
-        // Auto-equip item if the equipment slot is not used
-        if let Some(slot) = slot {
-            if get_equipped_in_slot(slot, &game.inventory).is_none() {
-                game.inventory[index].equip(&mut game.messages);
-            }
-        }
+        true
     }
 }
 
-pub struct Potion;
-
-impl Potion {
-    pub fn new(x: i32, y: i32, name: &str, item: Item) -> Object {
-        let mut object = Object::new(x, y, '!', &format!("{} Potion", name), VIOLET, false);
-        object.item = Some(item);
-        object.always_visible = true;
-        object
-    }
+pub fn pick_item_up(item_id: EntityId, game: &mut Game, world: &mut World) {
+    receive_item(item_id, None, game, world);
 }
 
-pub struct Scroll;
+// Take an item into the player's inventory, either off the floor or out of the
+// given container. Gold goes straight into their purse
+pub fn receive_item(item_id: EntityId, from: Option<EntityId>, game: &mut Game, world: &mut World) {
+    if world.items.get(item_id) == Some(&Item::Gold) {
+        let amount = count(item_id, world);
+        let message = match from {
+            Some(_) => format!("You take {} gold.", amount),
+            None => format!("You pick up {} gold.", amount),
+        };
+        game.messages.add(message, GOLD);
+        world.purses.get_or_insert_default(PLAYER).gold += amount;
+        if let Some(container) = from {
+            take_from_inventory(container, item_id, game, world);
+        }
+        world.despawn(item_id);
+        return;
+    }
+    if fits_in_inventory(item_id, game, world) {
+        let burdened = world.is_burdened(PLAYER);
+        let message = match (from, count(item_id, world)) {
+            (Some(container), _) => format!(
+                "You take {} out of the {}.",
+                describe(item_id, &game.knowledge, world),
+                world.names[container]
+            ),
+            (None, 1) => format!(
+                "You picked up a {}!",
+                item_name(item_id, &game.knowledge, world)
+            ),
+            (None, _) => format!(
+                "You picked up {}!",
+                describe(item_id, &game.knowledge, world)
+            ),
+        };
+        game.messages.add(message, GREEN);
+        if let Some(container) = from {
+            take_from_inventory(container, item_id, game, world);
+        }
+        check_theft(item_id, game, world);
+        let item_id = add_to_inventory(PLAYER, item_id, world);
+        if !burdened && world.is_burdened(PLAYER) {
+            game.messages
+                .add("You are burdened by the weight of your pack.", YELLOW);
+        }
 
-impl Scroll {
-    pub fn new(x: i32, y: i32, name: &str, item: Item) -> Object {
-        let mut object = Object::new(
-            x,
-            y,
-            '#',
-            &format!("Scroll of {}", name),
-            LIGHT_YELLOW,
-            false,
-        );
-        object.item = Some(item);
-        object.always_visible = true;
-        object
+        // Auto-equip item if the equipment slots are not used, unless it could be cursed
+        let known = world.equipment.get(item_id).is_some_and(|e| e.known);
+        if known && claim_slot(PLAYER, item_id, world).is_empty() {
+            world.equip(item_id, &mut game.messages);
+        }
     }
 }
 
+#[derive(Debug, Deserialize)]
 pub struct Transition {
     pub level: u32,
     pub value: u32,
@@ -282,3 +1005,88 @@ pub fn from_dungeon_level(table: &[Transition], level: u32) -> u32 {
         .find(|transition| level >= transition.level)
         .map_or(0, |transition| transition.value)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shop::Merchandise;
+
+    fn spawn_potions(world: &mut World, name: &str, count: i32) -> EntityId {
+        let id = world.spawn();
+        world.names.insert(id, name.into());
+        world.items.insert(id, Item::Heal { amount: 10 });
+        world.stacks.insert(id, Stack { count });
+        id
+    }
+
+    #[test]
+    fn plural_names() {
+        assert_eq!(plural("Healing Potion"), "Healing Potions");
+        assert_eq!(plural("Scroll of Fireball"), "Scrolls of Fireball");
+        assert_eq!(plural("scroll labeled XYZZY"), "scrolls labeled XYZZY");
+        assert_eq!(plural("arrows"), "arrows");
+    }
+
+    #[test]
+    fn only_identical_stackable_items_stack() {
+        let mut world = World::default();
+        let potions = spawn_potions(&mut world, "Healing Potion", 2);
+        let more = spawn_potions(&mut world, "Healing Potion", 1);
+        let other = spawn_potions(&mut world, "Potion of Speed", 1);
+        let sword = world.spawn();
+        world.names.insert(sword, "Healing Potion".into());
+        world.items.insert(sword, Item::Equip);
+        assert!(stacks_with(potions, more, &world));
+        assert!(!stacks_with(potions, potions, &world));
+        assert!(!stacks_with(potions, other, &world));
+        assert!(!stacks_with(potions, sword, &world));
+    }
+
+    #[test]
+    fn take_one_splits_a_stack() {
+        let mut game = Game::empty();
+        let mut world = World::default();
+        let owner = world.spawn();
+        let potions = spawn_potions(&mut world, "Healing Potion", 3);
+        world.inventories.insert(owner, vec![potions]);
+        let single = take_one(owner, potions, &mut game, &mut world);
+        assert_ne!(single, potions);
+        assert_eq!(count(single, &world), 1);
+        assert_eq!(count(potions, &world), 2);
+        assert_eq!(world.inventories[owner], vec![potions]);
+    }
+
+    #[test]
+    fn take_one_of_a_single_item_takes_it_out_of_the_inventory() {
+        let mut game = Game::empty();
+        let mut world = World::default();
+        let owner = world.spawn();
+        let potion = spawn_potions(&mut world, "Healing Potion", 1);
+        world.inventories.insert(owner, vec![potion]);
+        assert_eq!(take_one(owner, potion, &mut game, &mut world), potion);
+        assert!(world.inventories[owner].is_empty());
+    }
+
+    #[test]
+    fn placed_items_join_a_matching_pile() {
+        let mut world = World::default();
+        let pile = spawn_potions(&mut world, "Healing Potion", 2);
+        world.set_pos(pile, 3, 3);
+        let dropped = spawn_potions(&mut world, "Healing Potion", 1);
+        place_item(dropped, 3, 3, &mut world);
+        assert_eq!(count(pile, &world), 3);
+        assert!(!world.items.contains(dropped));
+    }
+
+    #[test]
+    fn wares_only_stack_with_the_same_shops_wares() {
+        let mut world = World::default();
+        let keeper = world.spawn();
+        let ware = spawn_potions(&mut world, "Healing Potion", 1);
+        world.wares.insert(ware, Merchandise { keeper });
+        let own = spawn_potions(&mut world, "Healing Potion", 1);
+        assert!(!stacks_with(ware, own, &world));
+        world.wares.insert(own, Merchandise { keeper });
+        assert!(stacks_with(ware, own, &world));
+    }
+}
@@ -0,0 +1,144 @@
+use crate::container::Container;
+use crate::entity::{EntityId, World};
+use crate::equipment::{Equipment, Slot};
+use crate::game::Game;
+use crate::item::{from_dungeon_level, Charges, Item, Stack, Transition};
+use crate::object::Renderable;
+use crate::ranged::{Ammo, AmmoKind, RangedWeapon};
+use rand::Rng;
+use serde::Deserialize;
+use tcod::Color;
+
+pub const ITEMS_FILE: &str = "data/items.json";
+
+// Chances for a piece of equipment to be found enchanted, or cursed with a
+// negative enchantment instead, and how far either way it can go
+const ENCHANT_CHANCE: f32 = 0.2;
+const CURSE_CHANCE: f32 = 0.1;
+const MAX_ENCHANTMENT: i32 = 3;
+
+// A kind of item as described in the data file
+#[derive(Debug, Deserialize)]
+pub struct ItemTemplate {
+    pub name: String,
+    pub glyph: char,
+    pub color: Color,
+    pub effect: Item,
+    pub equipment: Option<Equipment>,
+    pub ranged: Option<RangedWeapon>,
+    pub ammo: Option<AmmoBundle>,
+    // How many times a wand or staff can be used before it needs recharging
+    pub charges: Option<i32>,
+    // What shops sell it for. Items without a price can't be bought or sold
+    #[serde(default)]
+    pub price: i32,
+    // In pounds, for each one
+    #[serde(default)]
+    pub weight: f32,
+    // Spawn weight by dungeon level
+    pub spawn: Vec<Transition>,
+}
+
+// Ammunition is found in bundles of between `min` and `max` projectiles
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct AmmoBundle {
+    pub kind: AmmoKind,
+    pub min: i32,
+    pub max: i32,
+}
+
+impl ItemTemplate {
+    pub fn spawn_weight(&self, level: u32) -> u32 {
+        from_dungeon_level(&self.spawn, level)
+    }
+
+    pub fn spawn(&self, world: &mut World, x: i32, y: i32) -> EntityId {
+        let id = self.create(world);
+        world.set_pos(id, x, y);
+        id
+    }
+
+    // A new item of this kind that isn't anywhere yet, for putting straight into an inventory
+    pub fn create(&self, world: &mut World) -> EntityId {
+        let id = world.spawn();
+        world.names.insert(id, self.name.clone());
+        world.renderables.insert(
+            id,
+            Renderable {
+                glyph: self.glyph,
+                color: self.color,
+                always_visible: true,
+            },
+        );
+        world.items.insert(id, self.effect);
+        if self.price > 0 {
+            world.prices.insert(id, self.price);
+        }
+        if self.weight > 0.0 {
+            world.weights.insert(id, self.weight);
+        }
+        if self.effect == Item::Bag {
+            world.containers.insert(
+                id,
+                Container {
+                    locked: false,
+                    trap: None,
+                },
+            );
+            world.inventories.insert(id, vec![]);
+        }
+        if let Some(mut equipment) = self.equipment {
+            // Ranged weapons get their power from what they shoot
+            if equipment.slot != Slot::Ranged {
+                roll_enchantment(&mut equipment);
+            }
+            world.equipment.insert(id, equipment);
+        }
+        if let Some(ranged) = self.ranged {
+            world.ranged.insert(id, ranged);
+        }
+        if let Some(bundle) = self.ammo {
+            let count = rand::thread_rng().gen_range(bundle.min, bundle.max + 1);
+            world.ammo.insert(id, Ammo { kind: bundle.kind });
+            world.stacks.insert(id, Stack { count });
+        } else if let Some(max) = self.charges {
+            // Found with at least half its charges left
+            let current = rand::thread_rng().gen_range((max + 1) / 2, max + 1);
+            world.charges.insert(id, Charges { current, max });
+        } else if self.effect.stacks() {
+            world.stacks.insert(id, Stack { count: 1 });
+        }
+        id
+    }
+}
+
+fn roll_enchantment(equipment: &mut Equipment) {
+    let mut rng = rand::thread_rng();
+    let roll = rng.gen::<f32>();
+    if roll < CURSE_CHANCE {
+        equipment.enchantment = -rng.gen_range(1, MAX_ENCHANTMENT + 1);
+        equipment.cursed = true;
+    } else if roll < CURSE_CHANCE + ENCHANT_CHANCE {
+        equipment.enchantment = rng.gen_range(1, MAX_ENCHANTMENT + 1);
+    }
+}
+
+// Leave the guaranteed drops of slain uniques on their remains
+pub fn drop_loot(game: &Game, world: &mut World) {
+    let mut loot = vec![];
+    for (id, unique) in world.uniques.iter_mut() {
+        // The remains may have been picked up since the drops were left
+        let position = match world.positions.get(id) {
+            Some(&position) if !world.fighters.contains(id) => position,
+            _ => continue,
+        };
+        for name in unique.drops.drain(..) {
+            loot.push((name, position.x, position.y));
+        }
+    }
+    for (name, x, y) in loot {
+        if let Some(template) = game.templates.item(&name) {
+            template.spawn(world, x, y);
+        }
+    }
+}
@@ -1,31 +1,57 @@
 use tcod::colors::*;
 use tcod::console::*;
-use tcod::input::{self, Event, Key, Mouse};
+use tcod::input::{self, Event, Key, KeyCode, Mouse};
 use tcod::map::Map as FovMap;
 
 use std::error::Error;
 use std::fs::File;
 use std::io::{Read, Write};
 
+mod affix;
 mod ai;
+mod combat;
+mod container;
+mod damage;
+mod encumbrance;
+mod entity;
 mod equipment;
 mod game;
 mod gui;
+mod hunger;
+mod identify;
 mod item;
 mod log;
+mod loot;
 mod map;
+mod monster;
 mod object;
-
-use crate::ai::{ai_take_turn, mut_two, DeathCallback, Fighter};
+mod ranged;
+mod shop;
+mod status;
+
+use crate::affix::Rarity;
+use crate::ai::{run_ai, Ai, Awareness, DeathCallback, Faction, Fighter};
+use crate::container::open_chest;
+use crate::damage::{DamageType, Resistances};
+use crate::entity::{World, PLAYER};
 use crate::equipment::{Equipment, Slot};
-use crate::game::Game;
-use crate::gui::{render_bar, BAR_WIDTH, PANEL_HEIGHT, PANEL_Y};
-use crate::item::{pick_item_up, use_item, Item};
+use crate::game::{Game, Templates, MOVE_NOISE};
+use crate::gui::{draw_entities, render_bar, BAR_WIDTH, PANEL_HEIGHT, PANEL_Y};
+use crate::hunger::{Hunger, HungerState, FAINT_CHANCE, START_NUTRITION};
+use crate::identify::Knowledge;
+use crate::item::{drop_item, pick_item_up, throw_item, use_item, Item};
 use crate::log::{msgbox, MSG_HEIGHT, MSG_WIDTH, MSG_X};
+use crate::loot::drop_loot;
 use crate::map::*;
+use crate::monster::{announce_uniques, place_uniques};
 use crate::object::PlayerAction::*;
-use crate::object::{level_up, PlayerAction, LEVEL_UP_BASE, LEVEL_UP_FACTOR};
-use object::Object;
+use crate::object::{
+    free_spot_near, level_up, PlayerAction, Renderable, LEVEL_UP_BASE, LEVEL_UP_FACTOR,
+};
+use crate::ranged::fire_weapon;
+use crate::shop::{trade, Purse};
+use crate::status::StatusKind;
+use rand::Rng;
 
 // Actual window size
 pub const SCREEN_WIDTH: i32 = 80;
@@ -33,6 +59,9 @@ pub const SCREEN_HEIGHT: i32 = 50;
 
 const CHARACTER_SCREEN_WIDTH: i32 = 30;
 
+// How close allies need to be to follow the player to the next level
+const FOLLOW_STAIRS_DISTANCE: f32 = 3.0;
+
 // Max FPS
 const LIMIT_FPS: i32 = 20;
 
@@ -45,12 +74,12 @@ pub struct Tcod {
     pub mouse: Mouse,
 }
 
-fn render_all(tcod: &mut Tcod, game: &mut Game, objects: &[Object], fov_recompute: bool) {
+fn render_all(tcod: &mut Tcod, game: &mut Game, world: &World, fov_recompute: bool) {
     if fov_recompute {
-        let player = &objects[PLAYER];
+        let (player_x, player_y) = world.pos(PLAYER);
         tcod.fov.compute_fov(
-            player.x,
-            player.y,
+            player_x,
+            player_y,
             TORCH_RADIUS,
             FOV_LIGHT_WALLS,
             FOV_ALGORITHM,
@@ -81,19 +110,7 @@ fn render_all(tcod: &mut Tcod, game: &mut Game, objects: &[Object], fov_recomput
         }
     }
 
-    let mut to_draw: Vec<_> = objects
-        .iter()
-        .filter(|o| {
-            tcod.fov.is_in_fov(o.x, o.y)
-                || (o.always_visible && game.map[o.x as usize][o.y as usize].explored)
-        })
-        .collect();
-    // Sort so that non-blocking objects come first
-    to_draw.sort_by(|o1, o2| o1.blocks.cmp(&o2.blocks));
-    // Draw the objects in the list
-    for object in &to_draw {
-        object.draw(&mut tcod.con);
-    }
+    draw_entities(&mut tcod.con, &tcod.fov, &game.map, world);
 
     // Show player stats
     tcod.panel.set_default_background(BLACK);
@@ -112,8 +129,8 @@ fn render_all(tcod: &mut Tcod, game: &mut Game, objects: &[Object], fov_recomput
     }
 
     // Show player stats
-    let hp = objects[PLAYER].fighter.map_or(0, |f| f.hp);
-    let max_hp = objects[PLAYER].max_hp(game);
+    let hp = world.fighters.get(PLAYER).map_or(0, |f| f.hp);
+    let max_hp = world.max_hp(PLAYER);
     render_bar(
         &mut tcod.panel,
         1,
@@ -126,6 +143,20 @@ fn render_all(tcod: &mut Tcod, game: &mut Game, objects: &[Object], fov_recomput
         DARKER_RED,
     );
 
+    // Show how hungry the player is under the HP bar, once it starts to matter
+    let hunger = world.hunger_state(PLAYER);
+    if hunger != HungerState::Fed {
+        tcod.panel.set_default_foreground(hunger.color());
+        tcod.panel.print_ex(
+            1,
+            2,
+            BackgroundFlag::None,
+            TextAlignment::Left,
+            format!("{}", hunger),
+        );
+    }
+
+    tcod.panel.set_default_foreground(WHITE);
     tcod.panel.print_ex(
         1,
         3,
@@ -134,13 +165,39 @@ fn render_all(tcod: &mut Tcod, game: &mut Game, objects: &[Object], fov_recomput
         format!("Dungeon Level: {}", game.dungeon_level),
     );
 
+    let gold = world.purses.get(PLAYER).map_or(0, |p| p.gold);
+    tcod.panel.set_default_foreground(GOLD);
+    tcod.panel.print_ex(
+        1,
+        5,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        format!("Gold: {}", gold),
+    );
+
+    // Show any temporary effects on the player
+    let statuses = world.statuses.get(PLAYER).map_or(vec![], |statuses| {
+        statuses
+            .iter()
+            .map(|s| format!("{} ({})", s.kind, s.turns))
+            .collect()
+    });
+    tcod.panel.set_default_foreground(LIGHT_GREEN);
+    tcod.panel.print_ex(
+        1,
+        4,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        statuses.join(", "),
+    );
+
     tcod.panel.set_default_foreground(LIGHT_GREY);
     tcod.panel.print_ex(
         1,
         0,
         BackgroundFlag::None,
         TextAlignment::Left,
-        get_names_under_mouse(tcod.mouse, objects, &tcod.fov),
+        get_names_under_mouse(tcod.mouse, &game.knowledge, world, &tcod.fov),
     );
 
     blit(
@@ -167,39 +224,72 @@ fn render_all(tcod: &mut Tcod, game: &mut Game, objects: &[Object], fov_recomput
 fn player_move_or_attack(
     dx: i32,
     dy: i32,
+    tcod: &mut Tcod,
     game: &mut Game,
-    objects: &mut [Object],
+    world: &mut World,
 ) -> PlayerAction {
-    // Coords the player is moving to/attacking
-    let x = objects[PLAYER].x + dx;
-    let y = objects[PLAYER].y + dy;
+    // A confused player stumbles in a random direction instead
+    let (dx, dy) = if world.has_status(PLAYER, StatusKind::Confused) {
+        (
+            rand::thread_rng().gen_range(-1, 2),
+            rand::thread_rng().gen_range(-1, 2),
+        )
+    } else {
+        (dx, dy)
+    };
 
-    // Try to find an attack-able object
-    let target_id = objects
-        .iter()
-        .position(|object| object.fighter.is_some() && object.pos() == (x, y));
+    // Coords the player is moving to/attacking
+    let (player_x, player_y) = world.pos(PLAYER);
+    let (x, y) = (player_x + dx, player_y + dy);
+
+    // Try to find an attack-able entity
+    let target_id = world.at(x, y).find(|&id| world.fighters.contains(id));
+
+    // Chests stand in the way, so bumping into one opens it. Bags lying around don't
+    let chest = world
+        .at(x, y)
+        .find(|&id| world.containers.contains(id) && !world.items.contains(id));
+    if let Some(chest) = chest {
+        open_chest(chest, tcod, game, world);
+        return TookTurn;
+    }
 
     // Attack if target found, else move
     match target_id {
-        Some(target_id) => {
-            let (player, target) = mut_two(PLAYER, target_id, objects);
-            player.attack(target, game);
+        // Stumbled in place
+        Some(PLAYER) => {}
+        // Bumping into a shopkeeper who isn't after the player is a chance to trade
+        Some(target_id)
+            if world.shopkeepers.contains(target_id) && !world.is_hostile(PLAYER, target_id) =>
+        {
+            trade(target_id, tcod, game, world);
+            return DidntTakeTurn;
+        }
+        Some(target_id) if !world.is_hostile(PLAYER, target_id) => {
+            // Swap places with allies rather than attacking them
+            world.set_pos(PLAYER, x, y);
+            world.set_pos(target_id, player_x, player_y);
         }
+        Some(target_id) => world.attack(PLAYER, target_id, game),
+        // A burdened player only manages to move every other turn
+        None if world.is_burdened(PLAYER) && !game.turns.is_multiple_of(2) => {}
         None => {
-            Object::move_by(PLAYER, dx, dy, &game.map, objects);
+            world.move_by(PLAYER, dx, dy, &game.map);
+            let (x, y) = world.pos(PLAYER);
+            game.make_noise(x, y, MOVE_NOISE);
         }
     }
 
     TookTurn
 }
 
-fn next_level(tcod: &mut Tcod, game: &mut Game, objects: &mut Vec<Object>) {
+fn next_level(tcod: &mut Tcod, game: &mut Game, world: &mut World) {
     game.messages.add(
         "You take a moment to rest, and recover your strength.",
         VIOLET,
     );
-    let heal_hp = objects[PLAYER].max_hp(game) / 2;
-    objects[PLAYER].heal(heal_hp, game);
+    let heal_hp = world.max_hp(PLAYER) / 2;
+    world.heal(PLAYER, heal_hp);
 
     game.messages.add(
         "After a rare moment of peace, you descend deeper into \
@@ -207,39 +297,65 @@ fn next_level(tcod: &mut Tcod, game: &mut Game, objects: &mut Vec<Object>) {
         RED,
     );
     game.dungeon_level += 1;
-    game.map = Game::make_map(objects, game.dungeon_level);
+
+    // Allies close to the player follow them down the stairs, and are kept
+    // off the map until the new level is built
+    let mut followers = vec![];
+    for id in world.ais.ids() {
+        let following = id != PLAYER
+            && world.factions.get(id) == Some(&Faction::Player)
+            && world.distance_to(id, PLAYER) <= FOLLOW_STAIRS_DISTANCE;
+        if following {
+            world.positions.remove(id);
+            followers.push(id);
+        }
+    }
+
+    let mut survivors = vec![PLAYER];
+    survivors.extend(&followers);
+    game.map = Game::make_map(world, &survivors, game.dungeon_level, &game.templates);
+    // Nothing on the new level heard what happened on the old one
+    game.noises.clear();
+    place_uniques(game, world);
+    for id in followers {
+        let (player_x, player_y) = world.pos(PLAYER);
+        match free_spot_near(player_x, player_y, &game.map, world) {
+            Some((x, y)) => world.set_pos(id, x, y),
+            None => world.despawn(id),
+        }
+    }
     initialize_fov(tcod, &game.map);
 }
 
-fn handle_keys(tcod: &mut Tcod, game: &mut Game, objects: &mut Vec<Object>) -> PlayerAction {
+fn handle_keys(tcod: &mut Tcod, game: &mut Game, world: &mut World) -> PlayerAction {
     use tcod::input::KeyCode::*;
 
-    let player_alive = objects[PLAYER].alive;
+    let player_alive = world.is_alive(PLAYER);
     match (tcod.key, tcod.key.text(), player_alive) {
         // Movement
         (Key { code: Up, .. }, _, true) | (Key { code: NumPad8, .. }, _, true) => {
-            player_move_or_attack(0, -1, game, objects)
+            player_move_or_attack(0, -1, tcod, game, world)
         }
         (Key { code: Down, .. }, _, true) | (Key { code: NumPad2, .. }, _, true) => {
-            player_move_or_attack(0, 1, game, objects)
+            player_move_or_attack(0, 1, tcod, game, world)
         }
         (Key { code: Left, .. }, _, true) | (Key { code: NumPad4, .. }, _, true) => {
-            player_move_or_attack(-1, 0, game, objects)
+            player_move_or_attack(-1, 0, tcod, game, world)
         }
         (Key { code: Right, .. }, _, true) | (Key { code: NumPad6, .. }, _, true) => {
-            player_move_or_attack(1, 0, game, objects)
+            player_move_or_attack(1, 0, tcod, game, world)
         }
         (Key { code: Home, .. }, _, true) | (Key { code: NumPad7, .. }, _, true) => {
-            player_move_or_attack(-1, -1, game, objects)
+            player_move_or_attack(-1, -1, tcod, game, world)
         }
         (Key { code: PageUp, .. }, _, true) | (Key { code: NumPad9, .. }, _, true) => {
-            player_move_or_attack(1, -1, game, objects)
+            player_move_or_attack(1, -1, tcod, game, world)
         }
         (Key { code: End, .. }, _, true) | (Key { code: NumPad1, .. }, _, true) => {
-            player_move_or_attack(-1, 1, game, objects)
+            player_move_or_attack(-1, 1, tcod, game, world)
         }
         (Key { code: PageDown, .. }, _, true) | (Key { code: NumPad3, .. }, _, true) => {
-            player_move_or_attack(1, 1, game, objects)
+            player_move_or_attack(1, 1, tcod, game, world)
         }
         (Key { code: NumPad5, .. }, _, true) => {
             // Do nothing, i.e. wait for the monster to come to you
@@ -247,21 +363,20 @@ fn handle_keys(tcod: &mut Tcod, game: &mut Game, objects: &mut Vec<Object>) -> P
         }
         (Key { code: Text, .. }, "<", true) => {
             // Go down stairs if the player is on them
-            let player_on_stairs = objects
-                .iter()
-                .any(|object| object.pos() == objects[PLAYER].pos() && object.name == "Stairs");
+            let (player_x, player_y) = world.pos(PLAYER);
+            let player_on_stairs = world
+                .at(player_x, player_y)
+                .any(|id| world.names[id] == "Stairs");
             if player_on_stairs {
-                next_level(tcod, game, objects);
+                next_level(tcod, game, world);
             }
             DidntTakeTurn
         }
         (Key { code: Text, .. }, "c", true) => {
             // Show character stats
-            let player = &objects[PLAYER];
-            let level = player.level;
-            let level_up_xp = LEVEL_UP_BASE + player.level * LEVEL_UP_FACTOR;
-            if let Some(fighter) = player.fighter.as_ref() {
-                let msg = format!(
+            if let Some(fighter) = world.fighters.get(PLAYER) {
+                let level_up_xp = LEVEL_UP_BASE + fighter.level * LEVEL_UP_FACTOR;
+                let mut msg = format!(
                     "Character Stats:\n\
                      \n\
                      Level: {}\n\
@@ -271,48 +386,113 @@ fn handle_keys(tcod: &mut Tcod, game: &mut Game, objects: &mut Vec<Object>) -> P
                      Maximum HP: {}\n\
                      Attack: {}\n\
                      Defense: {}",
-                    level,
+                    fighter.level,
                     fighter.xp,
                     level_up_xp,
-                    player.max_hp(game),
-                    player.power(game),
-                    player.defense(game),
+                    world.max_hp(PLAYER),
+                    world.power(PLAYER),
+                    world.defense(PLAYER),
                 );
+                // List any resistances or vulnerabilities
+                for &damage_type in DamageType::ALL.iter() {
+                    let resistance = world.resistance(PLAYER, damage_type);
+                    if resistance != 0 {
+                        msg.push_str(&format!("\nResist {}: {}%", damage_type, resistance));
+                    }
+                }
+                if !game.history.is_empty() {
+                    msg.push_str("\n\nNotable kills:");
+                    for entry in &game.history {
+                        msg.push_str(&format!("\n{}", entry));
+                    }
+                }
                 msgbox(&msg, CHARACTER_SCREEN_WIDTH, &mut tcod.root);
             }
             DidntTakeTurn
         }
         (Key { code: Text, .. }, "d", true) => {
             // Show the inventory; If an item is selected, drop it
-            let inventory_index = inventory_menu(
-                &game.inventory,
-                "Press the key next to an item to drop it, or an other to cancel.\n'",
+            let item_id = inventory_menu(
+                &world.inventories[PLAYER],
+                &game.knowledge,
+                world,
+                &load_header(
+                    "Press the key next to an item to drop it, or an other to cancel.\n'",
+                    world,
+                ),
                 &mut tcod.root,
             );
-            if let Some(inventory_index) = inventory_index {
-                drop_item(inventory_index, game, objects);
+            if let Some(item_id) = item_id {
+                drop_item(item_id, game, world);
+            }
+            DidntTakeTurn
+        }
+        (Key { code: Text, .. }, "e", true) => {
+            // Show what's worn in each slot; If a slot is selected, take its item off
+            let item_id = equipment_menu(
+                PLAYER,
+                &game.knowledge,
+                world,
+                "Press the key next to a slot to take off what's in it, or any other to cancel.\n",
+                &mut tcod.root,
+            );
+            if let Some(item_id) = item_id {
+                if world.can_remove(item_id, &mut game.messages) {
+                    world.dequip(item_id, &mut game.messages);
+                }
             }
             DidntTakeTurn
         }
+        (Key { code: Text, .. }, "f", true) => {
+            // Fire the equipped ranged weapon at a target
+            if fire_weapon(tcod, game, world) {
+                TookTurn
+            } else {
+                DidntTakeTurn
+            }
+        }
         (Key { code: Text, .. }, "g", true) => {
             // Pick up an item
-            let item_id = objects
-                .iter()
-                .position(|object| object.pos() == objects[PLAYER].pos() && object.item.is_some());
+            let (player_x, player_y) = world.pos(PLAYER);
+            let item_id = world
+                .at(player_x, player_y)
+                .find(|&id| world.items.contains(id));
             if let Some(item_id) = item_id {
-                pick_item_up(item_id, game, objects);
+                pick_item_up(item_id, game, world);
             }
             DidntTakeTurn
         }
+        (Key { code: Text, .. }, "t", true) => {
+            // Show the inventory; If an item is selected, throw it
+            let item_id = inventory_menu(
+                &world.inventories[PLAYER],
+                &game.knowledge,
+                world,
+                &load_header(
+                    "Press the key next to an item to throw it, or any other to cancel.\n",
+                    world,
+                ),
+                &mut tcod.root,
+            );
+            match item_id {
+                Some(item_id) if throw_item(item_id, tcod, game, world) => TookTurn,
+                _ => DidntTakeTurn,
+            }
+        }
         (Key { code: Text, .. }, "i", true) => {
             // Show the inventory; If an item is selected, use it
-            let inventory_index = inventory_menu(
-                &game.inventory,
-                "Press the key next to an item to use it, or any other to cancel.\n",
+            let item_id = inventory_menu(
+                &world.inventories[PLAYER],
+                &game.knowledge,
+                world,
+                &load_header(
+                    "Press the key next to an item to use it, or any other to cancel.\n",
+                    world,
+                ),
                 &mut tcod.root,
             );
-            if let Some(inventory_index) = inventory_index {
-                use_item(inventory_index, tcod, game, objects);
+            if let Some(item_id) = item_id {
+                use_item(item_id, tcod, game, world);
             }
             DidntTakeTurn
         }
@@ -338,60 +518,112 @@ fn handle_keys(tcod: &mut Tcod, game: &mut Game, objects: &mut Vec<Object>) -> P
     }
 }
 
-fn get_names_under_mouse(mouse: Mouse, objects: &[Object], fov_map: &FovMap) -> String {
+fn get_names_under_mouse(
+    mouse: Mouse,
+    knowledge: &Knowledge,
+    world: &World,
+    fov_map: &FovMap,
+) -> String {
     let (x, y) = (mouse.cx as i32, mouse.cy as i32);
+    if !fov_map.is_in_fov(x, y) {
+        return String::new();
+    }
 
-    // Create a list with the names of all objects at the mouse's coordinates and in FOV
-    let names = objects
-        .iter()
-        .filter(|obj| obj.pos() == (x, y) && fov_map.is_in_fov(obj.x, obj.y))
-        .map(|obj| obj.name.clone())
+    // Create a list with the names of everything at the mouse's coordinates
+    let names = world
+        .at(x, y)
+        .map(|id| match world.awareness.get(id) {
+            Some(Awareness::Asleep) => format!("{} (asleep)", world.names[id]),
+            _ => knowledge.name(&world.names[id]).into(),
+        })
         .collect::<Vec<_>>();
 
     names.join(", ")
 }
 
-fn drop_item(inventory_id: usize, game: &mut Game, objects: &mut Vec<Object>) {
-    let mut item = game.inventory.remove(inventory_id);
-    if item.equipment.is_some() {
-        item.dequip(&mut game.messages);
-    }
-    item.set_pos(objects[PLAYER].x, objects[PLAYER].y);
-    game.messages
-        .add(format!("You dropped a {}.", item.name), YELLOW);
-    objects.push(item);
-}
-
-fn new_game(tcod: &mut Tcod) -> (Game, Vec<Object>) {
-    // Create object representing the player
-    let mut player = Object::new(0, 0, '@', "Player", WHITE, true);
-    player.alive = true;
-    player.fighter = Some(Fighter {
-        base_max_hp: 100,
-        hp: 100,
-        base_defense: 1,
-        base_power: 2,
-        xp: 0,
-        on_death: DeathCallback::Player,
-    });
-
-    // Create an object list
-    let mut objects = vec![player];
+fn new_game(tcod: &mut Tcod) -> (Game, World) {
+    let mut world = World::default();
+
+    // Create the player, the first entity in the world
+    let player = world.spawn_at(0, 0, '@', "Player", WHITE, true);
+    world.factions.insert(player, Faction::Player);
+    world.fighters.insert(
+        player,
+        Fighter {
+            base_max_hp: 100,
+            hp: 100,
+            base_defense: 1,
+            base_power: 2,
+            xp: 0,
+            level: 1,
+            resistances: Resistances::default(),
+            on_death: DeathCallback::Player,
+        },
+    );
+    world.inventories.insert(player, vec![]);
+    world.hungers.insert(
+        player,
+        Hunger {
+            nutrition: START_NUTRITION,
+        },
+    );
+    world.purses.insert(player, Purse::default());
 
     // Create the game
-    let mut game = Game::new(&mut objects);
+    let mut game = Game::new(&mut world);
+
+    // A loyal dog starts out next to the player
+    let (player_x, player_y) = world.pos(player);
+    if let Some((x, y)) = free_spot_near(player_x, player_y, &game.map, &world) {
+        let dog = world.spawn_at(x, y, 'd', "Dog", LIGHT_AMBER, true);
+        world.fighters.insert(
+            dog,
+            Fighter {
+                base_max_hp: 20,
+                hp: 20,
+                base_defense: 0,
+                base_power: 3,
+                xp: 0,
+                level: 1,
+                resistances: Resistances::default(),
+                on_death: DeathCallback::Monster,
+            },
+        );
+        world.ais.insert(dog, Ai::Ally);
+        world.factions.insert(dog, Faction::Player);
+    }
 
     // Initial equipment: dagger
-    let mut dagger = Object::new(0, 0, '-', "dagger", SKY, false);
-    dagger.item = Some(Item::Sword);
-    dagger.equipment = Some(Equipment {
-        equipped: true,
-        slot: Slot::LeftHand,
-        max_hp_bonus: 0,
-        defense_bonus: 0,
-        power_bonus: 2,
-    });
-    game.inventory.push(dagger);
+    let dagger = world.spawn();
+    world.names.insert(dagger, "dagger".into());
+    world.renderables.insert(
+        dagger,
+        Renderable {
+            glyph: '-',
+            color: SKY,
+            always_visible: false,
+        },
+    );
+    world.items.insert(dagger, Item::Equip);
+    world.weights.insert(dagger, 2.0);
+    world.equipment.insert(
+        dagger,
+        Equipment {
+            equipped: true,
+            slot: Slot::RightHand,
+            max_hp_bonus: 0,
+            defense_bonus: 0,
+            power_bonus: 2,
+            resistances: Resistances::default(),
+            two_handed: false,
+            enchantment: 0,
+            cursed: false,
+            known: true,
+            rarity: Rarity::Common,
+            on_hit: None,
+        },
+    );
+    world.inventories[player].push(dagger);
 
     initialize_fov(tcod, &game.map);
 
@@ -401,7 +633,7 @@ fn new_game(tcod: &mut Tcod) -> (Game, Vec<Object>) {
         RED,
     );
 
-    (game, objects)
+    (game, world)
 }
 
 pub fn initialize_fov(tcod: &mut Tcod, map: &Map) {
@@ -421,14 +653,14 @@ pub fn initialize_fov(tcod: &mut Tcod, map: &Map) {
     }
 }
 
-fn save_game(game: &Game, objects: &[Object]) -> Result<(), Box<dyn Error>> {
-    let save_data = serde_json::to_string(&(game, objects))?;
+fn save_game(game: &Game, world: &World) -> Result<(), Box<dyn Error>> {
+    let save_data = serde_json::to_string(&(game, world))?;
     let mut file = File::create("savegame")?;
     file.write_all(save_data.as_bytes())?;
     Ok(())
 }
 
-fn play_game(tcod: &mut Tcod, game: &mut Game, objects: &mut Vec<Object>) {
+fn play_game(tcod: &mut Tcod, game: &mut Game, world: &mut World) {
     // Force FOV to "recompute" the first time through the game loop
     let mut previous_player_position = (-1, -1);
 
@@ -441,37 +673,59 @@ fn play_game(tcod: &mut Tcod, game: &mut Game, objects: &mut Vec<Object>) {
             _ => tcod.key = Default::default(),
         }
 
-        let fov_recompute = previous_player_position != (objects[PLAYER].pos());
-        render_all(tcod, game, &objects, fov_recompute);
+        let fov_recompute = previous_player_position != world.pos(PLAYER);
+        render_all(tcod, game, world, fov_recompute);
+        announce_uniques(|x, y| tcod.fov.is_in_fov(x, y), game, world);
         tcod.root.flush();
 
         // Level up if needed
-        level_up(tcod, game, objects);
-
-        previous_player_position = objects[PLAYER].pos();
-        let player_action = handle_keys(tcod, game, objects);
+        level_up(tcod, game, world);
+
+        previous_player_position = world.pos(PLAYER);
+        // Only roll to faint when the player actually tries to do something, and
+        // never stand in the way of saving and quitting
+        let acting = !matches!(tcod.key.code, KeyCode::NoKey | KeyCode::Escape);
+        let fainting = world.hunger_state(PLAYER) == HungerState::Fainting;
+        let player_action =
+            if acting && fainting && world.is_alive(PLAYER) && rand::random::<f32>() < FAINT_CHANCE
+            {
+                // Pass out for a turn, leaving the monsters to do as they please
+                game.messages.add("You faint from lack of food.", RED);
+                TookTurn
+            } else {
+                handle_keys(tcod, game, world)
+            };
         if player_action == Exit {
-            save_game(game, objects).unwrap();
+            save_game(game, world).unwrap();
             break;
         }
 
-        // Let monsters take their turn
-        if objects[PLAYER].alive && player_action != DidntTakeTurn {
-            for id in 0..objects.len() {
-                if objects[id].ai.is_some() {
-                    ai_take_turn(id, &tcod, game, objects);
-                }
+        // Let monsters take their turn. A hasted player gets two moves for each of theirs
+        if world.is_alive(PLAYER) && player_action != DidntTakeTurn {
+            game.turns += 1;
+            if !world.has_status(PLAYER, StatusKind::Hasted) || game.turns.is_multiple_of(2) {
+                run_ai(tcod, game, world);
+                // Noises carry over until the monsters have had a chance to hear them
+                game.noises.clear();
+            }
+            world.suffer_poison(PLAYER, game);
+            world.digest(PLAYER, game);
+            for kind in world.tick_statuses(PLAYER) {
+                game.messages
+                    .add(format!("You are no longer {}.", kind), LIGHT_GREY);
             }
         }
+        drop_loot(game, world);
     }
 }
 
-fn load_game() -> Result<(Game, Vec<Object>), Box<dyn Error>> {
+fn load_game() -> Result<(Game, World), Box<dyn Error>> {
     let mut json_save_state = String::new();
     let mut file = File::open("savegame")?;
     file.read_to_string(&mut json_save_state)?;
-    let result = serde_json::from_str::<(Game, Vec<Object>)>(&json_save_state)?;
-    Ok(result)
+    let (mut game, world) = serde_json::from_str::<(Game, World)>(&json_save_state)?;
+    game.templates = Templates::load();
+    Ok((game, world))
 }
 
 fn main_menu(tcod: &mut Tcod) {
@@ -505,15 +759,15 @@ fn main_menu(tcod: &mut Tcod) {
 
         match choice {
             Some(0) => {
-                let (mut game, mut objects) = new_game(tcod);
-                play_game(tcod, &mut game, &mut objects);
+                let (mut game, mut world) = new_game(tcod);
+                play_game(tcod, &mut game, &mut world);
             }
             Some(1) => {
                 // Load game
                 match load_game() {
-                    Ok((mut game, mut objects)) => {
+                    Ok((mut game, mut world)) => {
                         initialize_fov(tcod, &game.map);
-                        play_game(tcod, &mut game, &mut objects);
+                        play_game(tcod, &mut game, &mut world);
                     }
                     Err(_e) => {
                         msgbox("\nNo saved game to load.\n", 24, &mut tcod.root);
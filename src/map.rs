@@ -1,16 +1,18 @@
+use crate::entity::{EntityId, World, PLAYER};
+use crate::equipment::{get_equipped_in_slot, Slot};
+use crate::identify::{item_name, Knowledge};
+use crate::item::describe;
 use crate::map::TunnelDirection::{Horizontal, Vertical};
-use crate::object::Object;
 use crate::{SCREEN_HEIGHT, SCREEN_WIDTH};
 use std::cmp;
 use tcod::colors::WHITE;
 use tcod::console::{blit, Offscreen, Root};
+use tcod::line::Line;
 use tcod::map::FovAlgorithm;
 use tcod::{BackgroundFlag, Color, Console, TextAlignment};
 
 use serde::{Deserialize, Serialize};
 
-pub const PLAYER: usize = 0;
-
 pub const MAP_WIDTH: i32 = 80;
 pub const MAP_HEIGHT: i32 = 43;
 
@@ -132,98 +134,226 @@ pub fn create_vert_tunnel(y1: i32, y2: i32, x: i32, map: &mut Map) {
 
 pub type Map = Vec<Vec<Tile>>;
 
+// Whether nothing between the two points blocks sight. The end points themselves
+// don't count, so a creature standing in a doorway can still be seen
+pub fn line_of_sight(from: (i32, i32), to: (i32, i32), map: &Map) -> bool {
+    Line::new(from, to)
+        .take_while(|&pos| pos != to)
+        .all(|(x, y)| !map[x as usize][y as usize].block_sight)
+}
+
+// Menus letter their options from a to z
+const MENU_PAGE_SIZE: usize = 26;
+
 pub fn menu<T: AsRef<str>>(
     header: &str,
     options: &[T],
     width: i32,
     root: &mut Root,
 ) -> Option<usize> {
-    assert!(
-        options.len() <= 26,
-        "Cannot have a menu with more than 26 options."
-    );
+    let options = options
+        .iter()
+        .map(|option| (option.as_ref(), WHITE))
+        .collect::<Vec<_>>();
+    colored_menu(header, &options, width, root)
+}
 
-    // Calculate total height for the header (after auto-wrap) and one line per option
-    let header_height = if header.is_empty() {
-        0
-    } else {
-        root.get_height_rect(0, 0, width, SCREEN_HEIGHT, header)
-    };
-    let height = options.len() as i32 + header_height;
-
-    // Create an off-screen console that represents the menu's window
-    let mut window = Offscreen::new(width, height);
-
-    // Print the header, with auto-wrap
-    window.set_default_foreground(WHITE);
-    window.print_rect_ex(
-        0,
-        0,
-        width,
-        height,
-        BackgroundFlag::None,
-        TextAlignment::Left,
-        header,
+// A menu where each option is printed in its own color. Options past what the
+// letters can cover go on further pages, turned with < and >
+pub fn colored_menu<T: AsRef<str>>(
+    header: &str,
+    options: &[(T, Color)],
+    width: i32,
+    root: &mut Root,
+) -> Option<usize> {
+    let pages = options.len().div_ceil(MENU_PAGE_SIZE).max(1);
+    let mut page = 0;
+
+    // Keep what's under the menu, to put back before showing another page
+    let mut background = Offscreen::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+    blit(
+        root,
+        (0, 0),
+        (SCREEN_WIDTH, SCREEN_HEIGHT),
+        &mut background,
+        (0, 0),
+        1.0,
+        1.0,
     );
 
-    // Print all the options
-    for (index, option_text) in options.iter().enumerate() {
-        let menu_letter = (b'a' + index as u8) as char;
-        let text = format!("({}) {}", menu_letter, option_text.as_ref());
-        window.print_ex(
+    loop {
+        let first = page * MENU_PAGE_SIZE;
+        let shown = &options[first..cmp::min(first + MENU_PAGE_SIZE, options.len())];
+        let header = if pages > 1 {
+            format!(
+                "{}Page {} of {}, < and > to turn.\n",
+                header,
+                page + 1,
+                pages
+            )
+        } else {
+            header.to_string()
+        };
+
+        // Calculate total height for the header (after auto-wrap) and one line per option
+        let header_height = if header.is_empty() {
+            0
+        } else {
+            root.get_height_rect(0, 0, width, SCREEN_HEIGHT, &header)
+        };
+        let height = shown.len() as i32 + header_height;
+
+        // Create an off-screen console that represents the menu's window
+        let mut window = Offscreen::new(width, height);
+
+        // Print the header, with auto-wrap
+        window.set_default_foreground(WHITE);
+        window.print_rect_ex(
+            0,
             0,
-            header_height + index as i32,
+            width,
+            height,
             BackgroundFlag::None,
             TextAlignment::Left,
-            text,
+            &header,
         );
-    }
 
-    // Blit the contents of "window" to the root console
-    let x = SCREEN_WIDTH / 2 - width / 2;
-    let y = SCREEN_HEIGHT / 2 - height / 2;
-    blit(&window, (0, 0), (width, height), root, (x, y), 1.0, 0.7);
+        // Print all the options on this page
+        for (index, (option_text, color)) in shown.iter().enumerate() {
+            let menu_letter = (b'a' + index as u8) as char;
+            let text = format!("({}) {}", menu_letter, option_text.as_ref());
+            window.set_default_foreground(*color);
+            window.print_ex(
+                0,
+                header_height + index as i32,
+                BackgroundFlag::None,
+                TextAlignment::Left,
+                text,
+            );
+        }
 
-    // Present the root console to the player and wait for a key-press
-    root.flush();
-    let key = root.wait_for_keypress(true);
+        // Blit the contents of "window" to the root console
+        blit(
+            &background,
+            (0, 0),
+            (SCREEN_WIDTH, SCREEN_HEIGHT),
+            root,
+            (0, 0),
+            1.0,
+            1.0,
+        );
+        let x = SCREEN_WIDTH / 2 - width / 2;
+        let y = SCREEN_HEIGHT / 2 - height / 2;
+        blit(&window, (0, 0), (width, height), root, (x, y), 1.0, 0.7);
 
-    // Convert the ASCII code to an index; If it corresponds to an option, return it
-    if key.printable.is_alphabetic() {
-        let index = key.printable.to_ascii_lowercase() as usize - 'a' as usize;
-        if index < options.len() {
-            Some(index)
-        } else {
-            None
+        // Present the root console to the player and wait for a key-press
+        root.flush();
+        let key = root.wait_for_keypress(true);
+
+        // Turn the page, or convert the ASCII code to an index; If it corresponds
+        // to an option, return it
+        match key.printable {
+            '>' => page = cmp::min(page + 1, pages - 1),
+            '<' => page = page.saturating_sub(1),
+            letter if letter.is_ascii_alphabetic() => {
+                let index = letter.to_ascii_lowercase() as usize - 'a' as usize;
+                return if index < shown.len() {
+                    Some(first + index)
+                } else {
+                    None
+                };
+            }
+            _ => return None,
         }
-    } else {
-        None
     }
 }
 
 const INVENTORY_WIDTH: i32 = 50;
 
-pub fn inventory_menu(inventory: &[Object], header: &str, root: &mut Root) -> Option<usize> {
-    let options = if inventory.len() == 0 {
-        vec!["Inventory is empty.".into()]
+// Magic and rare equipment stands out in menus as it does on the map
+fn rarity_color(id: EntityId, world: &World) -> Color {
+    world
+        .equipment
+        .get(id)
+        .and_then(|equipment| equipment.rarity.color())
+        .unwrap_or(WHITE)
+}
+
+const EQUIPMENT_WIDTH: i32 = 50;
+
+// A header for a menu of the player's own things, saying how much they carry
+pub fn load_header(header: &str, world: &World) -> String {
+    format!(
+        "{}Carrying {:.1} of {:.0} lbs.\n",
+        header,
+        world.carried_weight(PLAYER),
+        world.carry_capacity(PLAYER)
+    )
+}
+
+pub fn inventory_menu(
+    inventory: &[EntityId],
+    knowledge: &Knowledge,
+    world: &World,
+    header: &str,
+    root: &mut Root,
+) -> Option<EntityId> {
+    let options = if inventory.is_empty() {
+        vec![("Inventory is empty.".into(), WHITE)]
     } else {
         inventory
             .iter()
-            .map(|item| match item.equipment {
-                Some(equipment) if equipment.equipped => {
-                    format!("{} (on {})", item.name, equipment.slot)
-                }
-                _ => item.name.clone(),
+            .map(|&id| {
+                let name = describe(id, knowledge, world);
+                let text = match (world.equipment.get(id), world.charges.get(id)) {
+                    (Some(equipment), _) if equipment.equipped => {
+                        format!("{} (on {})", name, equipment.slot)
+                    }
+                    (_, Some(charges)) => format!("{} ({}/{})", name, charges.current, charges.max),
+                    _ if world.containers.contains(id) => {
+                        format!("{} ({} inside)", name, world.inventories[id].len())
+                    }
+                    _ => name,
+                };
+                (text, rarity_color(id, world))
             })
             .collect()
     };
 
-    let inventory_index = menu(header, &options, INVENTORY_WIDTH, root);
+    let inventory_index = colored_menu(header, &options, INVENTORY_WIDTH, root);
 
     // If an item was chosen, return it
-    if inventory.len() > 0 {
-        inventory_index
+    if !inventory.is_empty() {
+        inventory_index.map(|index| inventory[index])
     } else {
         None
     }
 }
+
+// Every slot and what's worn in it. Returns the item in the chosen slot, if any
+pub fn equipment_menu(
+    owner: EntityId,
+    knowledge: &Knowledge,
+    world: &World,
+    header: &str,
+    root: &mut Root,
+) -> Option<EntityId> {
+    let worn = Slot::ALL
+        .iter()
+        .map(|&slot| get_equipped_in_slot(slot, owner, world))
+        .collect::<Vec<_>>();
+    let options = Slot::ALL
+        .iter()
+        .zip(&worn)
+        .map(|(slot, item)| match *item {
+            Some(item_id) => (
+                format!("{}: {}", slot, item_name(item_id, knowledge, world)),
+                rarity_color(item_id, world),
+            ),
+            None => (format!("{}: -", slot), WHITE),
+        })
+        .collect::<Vec<_>>();
+
+    let index = colored_menu(header, &options, EQUIPMENT_WIDTH, root);
+    index.and_then(|index| worn[index])
+}
@@ -0,0 +1,197 @@
+use crate::ai::{Ability, Ai, Awareness, DeathCallback, Faction, Fighter, Vision};
+use crate::damage::Resistances;
+use crate::entity::{EntityId, World, PLAYER};
+use crate::game::Game;
+use crate::item::{add_to_inventory, equip_if_free, from_dungeon_level, Transition};
+use crate::loot::ItemTemplate;
+use crate::map::{Map, MAP_HEIGHT, MAP_WIDTH};
+use crate::object::is_blocked;
+use crate::shop::Purse;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tcod::colors::LIGHT_RED;
+use tcod::Color;
+
+pub const MONSTERS_FILE: &str = "data/monsters.json";
+pub const UNIQUES_FILE: &str = "data/uniques.json";
+
+// Uniques are placed at least this far from where the player arrives
+const UNIQUE_MIN_DISTANCE: f32 = 10.0;
+const UNIQUE_PLACEMENT_TRIES: usize = 100;
+
+// A kind of monster as described in the data file
+#[derive(Debug, Deserialize)]
+pub struct MonsterTemplate {
+    pub name: String,
+    pub glyph: char,
+    pub color: Color,
+    pub hp: i32,
+    pub defense: i32,
+    pub power: i32,
+    pub xp: i32,
+    pub ai: Ai,
+    pub faction: Faction,
+    #[serde(default)]
+    pub resistances: Resistances,
+    #[serde(default)]
+    pub vision: Vision,
+    #[serde(default)]
+    pub abilities: Vec<Ability>,
+    // Whether it picks up and uses items, and the item templates it starts out with
+    #[serde(default)]
+    pub carries: bool,
+    #[serde(default)]
+    pub inventory: Vec<String>,
+    // The most gold it can be carrying
+    #[serde(default)]
+    pub gold: i32,
+    // Spawn weight by dungeon level, and the deepest level it appears on
+    #[serde(default)]
+    pub spawn: Vec<Transition>,
+    pub max_level: Option<u32>,
+}
+
+impl MonsterTemplate {
+    pub fn spawn_weight(&self, level: u32) -> u32 {
+        match self.max_level {
+            Some(max_level) if level > max_level => 0,
+            _ => from_dungeon_level(&self.spawn, level),
+        }
+    }
+
+    pub fn spawn(&self, world: &mut World, x: i32, y: i32, items: &[ItemTemplate]) -> EntityId {
+        let id = world.spawn_at(x, y, self.glyph, &self.name, self.color, true);
+        world.fighters.insert(
+            id,
+            Fighter {
+                base_max_hp: self.hp,
+                hp: self.hp,
+                base_defense: self.defense,
+                base_power: self.power,
+                xp: self.xp,
+                level: 1,
+                resistances: self.resistances,
+                on_death: DeathCallback::Monster,
+            },
+        );
+        world.ais.insert(id, self.ai.clone());
+        world.factions.insert(id, self.faction);
+        world.visions.insert(id, self.vision);
+        if !self.abilities.is_empty() {
+            world.abilities.insert(id, self.abilities.clone());
+        }
+        if self.gold > 0 {
+            let gold = rand::thread_rng().gen_range(0, self.gold + 1);
+            world.purses.insert(id, Purse { gold });
+        }
+        if self.carries {
+            world.inventories.insert(id, vec![]);
+            for name in &self.inventory {
+                if let Some(template) = items.iter().find(|t| &t.name == name) {
+                    let item_id = template.create(world);
+                    let item_id = add_to_inventory(id, item_id, world);
+                    equip_if_free(id, item_id, world);
+                }
+            }
+        }
+        id
+    }
+}
+
+// A one-of-a-kind monster, waiting for the player on a given dungeon level
+#[derive(Debug, Deserialize)]
+pub struct UniqueTemplate {
+    #[serde(flatten)]
+    pub monster: MonsterTemplate,
+    pub level: u32,
+    pub announcement: String,
+    // Names of item templates it always carries
+    #[serde(default)]
+    pub drops: Vec<String>,
+}
+
+impl UniqueTemplate {
+    pub fn spawn(&self, world: &mut World, x: i32, y: i32, items: &[ItemTemplate]) -> EntityId {
+        let id = self.monster.spawn(world, x, y, items);
+        world.awareness.insert(id, Awareness::Unaware);
+        world.uniques.insert(
+            id,
+            Unique {
+                announcement: Some(self.announcement.clone()),
+                drops: self.drops.clone(),
+            },
+        );
+        id
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Unique {
+    // Shown the first time the player sees it
+    pub announcement: Option<String>,
+    pub drops: Vec<String>,
+}
+
+// Put any uniques that belong on the current level, unless they already appeared this run
+pub fn place_uniques(game: &mut Game, world: &mut World) {
+    for unique in &game.templates.uniques {
+        let name = &unique.monster.name;
+        if unique.level != game.dungeon_level || game.uniques_spawned.contains(name) {
+            continue;
+        }
+        if let Some((x, y)) = remote_spot(&game.map, world) {
+            unique.spawn(world, x, y, &game.templates.items);
+            game.uniques_spawned.push(name.clone());
+        }
+    }
+}
+
+// A random free tile well away from the player
+fn remote_spot(map: &Map, world: &World) -> Option<(i32, i32)> {
+    for _ in 0..UNIQUE_PLACEMENT_TRIES {
+        let x = rand::thread_rng().gen_range(0, MAP_WIDTH);
+        let y = rand::thread_rng().gen_range(0, MAP_HEIGHT);
+        if !is_blocked(x, y, map, world) && world.distance(PLAYER, x, y) >= UNIQUE_MIN_DISTANCE {
+            return Some((x, y));
+        }
+    }
+    None
+}
+
+pub fn announce_uniques(in_view: impl Fn(i32, i32) -> bool, game: &mut Game, world: &mut World) {
+    for (id, unique) in world.uniques.iter_mut() {
+        // The remains may have been picked up or put away in a bag
+        let position = match world.positions.get(id) {
+            Some(&position) => position,
+            None => continue,
+        };
+        if !in_view(position.x, position.y) {
+            continue;
+        }
+        if let Some(announcement) = unique.announcement.take() {
+            game.messages.add(announcement, LIGHT_RED);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uniques_without_a_position_are_not_announced() {
+        let mut game = Game::empty();
+        let mut world = World::default();
+        // Like a unique's corpse that was picked up
+        let id = world.spawn();
+        world.uniques.insert(
+            id,
+            Unique {
+                announcement: Some("It stirs.".into()),
+                drops: vec![],
+            },
+        );
+        announce_uniques(|_, _| true, &mut game, &mut world);
+        assert!(world.uniques[id].announcement.is_some());
+    }
+}
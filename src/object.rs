@@ -1,5 +1,8 @@
-use crate::ai::{Ai, DeathCallback, Fighter};
-use crate::map::{menu, Map, Rect, MAP_HEIGHT, MAP_WIDTH, PLAYER};
+use crate::affix::roll_affixes;
+use crate::ai::Awareness;
+use crate::entity::{EntityId, World, PLAYER};
+use crate::map::{menu, Map, Rect, MAP_HEIGHT, MAP_WIDTH};
+use crate::shop::roll_gold;
 use crate::{render_all, Tcod};
 use rand::distributions::WeightedIndex;
 use rand::prelude::*;
@@ -7,18 +10,19 @@ use rand::Rng;
 use tcod::colors::*;
 use tcod::input::Event;
 
-use crate::equipment::{Equipment, Slot};
-use crate::game::Game;
-use crate::item::{from_dungeon_level, Item, Potion, Scroll, Transition};
-use crate::log::Messages;
+use crate::game::{Game, Templates};
+use crate::item::{from_dungeon_level, Transition};
 use serde::{Deserialize, Serialize};
-use tcod::{input, BackgroundFlag, Console};
+use tcod::input;
 
 pub const LEVEL_UP_BASE: i32 = 200;
 pub const LEVEL_UP_FACTOR: i32 = 150;
 
 const LEVEL_SCREEN_WIDTH: i32 = 40;
 
+// Chance for a monster to be generated asleep rather than just unaware
+const SLEEP_CHANCE: f32 = 0.5;
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum PlayerAction {
     TookTurn,
@@ -29,17 +33,18 @@ pub enum PlayerAction {
 pub fn target_monster(
     tcod: &mut Tcod,
     game: &mut Game,
-    objects: &[Object],
+    world: &World,
     max_range: Option<f32>,
-) -> Option<usize> {
+) -> Option<EntityId> {
     loop {
-        match target_tile(tcod, game, objects, max_range) {
+        match target_tile(tcod, game, world, max_range) {
             Some((x, y)) => {
                 // Return the first clicked monster, otherwise continue looping
-                for (id, obj) in objects.iter().enumerate() {
-                    if obj.pos() == (x, y) && obj.fighter.is_some() && id != PLAYER {
-                        return Some(id);
-                    }
+                let monster = world
+                    .at(x, y)
+                    .find(|&id| world.fighters.contains(id) && id != PLAYER);
+                if monster.is_some() {
+                    return monster;
                 }
             }
             None => return None,
@@ -47,19 +52,20 @@ pub fn target_monster(
     }
 }
 
-pub fn closest_monster(tcod: &Tcod, objects: &[Object], max_range: i32) -> Option<usize> {
+pub fn closest_monster(tcod: &Tcod, world: &World, max_range: i32) -> Option<EntityId> {
     let mut closest_enemy = None;
     // Start with (slightly more than) maximum range
     let mut closest_dist = (max_range + 1) as f32;
 
-    for (id, object) in objects.iter().enumerate() {
+    for (id, _) in world.ais.iter() {
+        let (x, y) = world.pos(id);
         if id != PLAYER
-            && object.fighter.is_some()
-            && object.ai.is_some()
-            && tcod.fov.is_in_fov(object.x, object.y)
+            && world.fighters.contains(id)
+            && world.is_hostile(PLAYER, id)
+            && tcod.fov.is_in_fov(x, y)
         {
-            // Calculate distance between this object and the player
-            let dist = objects[PLAYER].distance_to(object);
+            // Calculate distance between this monster and the player
+            let dist = world.distance_to(PLAYER, id);
             if dist < closest_dist {
                 // It's closer, so remember it
                 closest_enemy = Some(id);
@@ -70,21 +76,20 @@ pub fn closest_monster(tcod: &Tcod, objects: &[Object], max_range: i32) -> Optio
     closest_enemy
 }
 
-pub fn level_up(tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) {
-    let player = &mut objects[PLAYER];
-    let level_up_xp = LEVEL_UP_BASE + player.level * LEVEL_UP_FACTOR;
+pub fn level_up(tcod: &mut Tcod, game: &mut Game, world: &mut World) {
+    let fighter = &mut world.fighters[PLAYER];
+    let level_up_xp = LEVEL_UP_BASE + fighter.level * LEVEL_UP_FACTOR;
     // See if the player's xp is enough to level up
-    if player.fighter.as_ref().map_or(0, |f| f.xp) >= level_up_xp {
+    if fighter.xp >= level_up_xp {
         // Level up
-        player.level += 1;
+        fighter.level += 1;
         game.messages.add(
             format!(
                 "Your battle skills grow stronger! You reached level {}",
-                player.level
+                fighter.level
             ),
             YELLOW,
         );
-        let fighter = player.fighter.as_mut().unwrap();
         let mut choice = None;
         while choice.is_none() {
             // Keep asking until a choice is made
@@ -116,227 +121,93 @@ pub fn level_up(tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Object {
+// Where an entity is on the map. Anything carried has no position
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Position {
     pub x: i32,
     pub y: i32,
-    pub char: char,
+}
+
+// How an entity is drawn
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Renderable {
+    pub glyph: char,
     pub color: Color,
-    pub name: String,
-    pub blocks: bool,
-    pub alive: bool,
-    pub fighter: Option<Fighter>,
-    pub ai: Option<Ai>,
-    pub item: Option<Item>,
+    // Stays on screen after going out of sight, once its tile is explored
     pub always_visible: bool,
-    pub level: i32,
-    pub equipment: Option<Equipment>,
 }
 
-impl Object {
-    pub fn new(x: i32, y: i32, char: char, name: &str, color: Color, blocks: bool) -> Self {
-        Object {
-            x,
-            y,
-            char,
-            color,
-            name: name.into(),
-            blocks,
-            alive: false,
-            fighter: None,
-            ai: None,
-            item: None,
-            always_visible: false,
-            level: 1,
-            equipment: None,
+// Nothing else can stand on the same tile as an entity that blocks
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Blocks;
+
+impl World {
+    // A new entity with what every entity on the map needs
+    pub fn spawn_at(
+        &mut self,
+        x: i32,
+        y: i32,
+        glyph: char,
+        name: &str,
+        color: Color,
+        blocks: bool,
+    ) -> EntityId {
+        let id = self.spawn();
+        self.positions.insert(id, Position { x, y });
+        self.renderables.insert(
+            id,
+            Renderable {
+                glyph,
+                color,
+                always_visible: false,
+            },
+        );
+        self.names.insert(id, name.into());
+        if blocks {
+            self.blockers.insert(id, Blocks);
         }
+        id
     }
 
-    pub fn power(&self, game: &Game) -> i32 {
-        let base_power = self.fighter.map_or(0, |f| f.base_power);
-        let bonus: i32 = self
-            .get_all_equipped(game)
-            .iter()
-            .map(|e| e.power_bonus)
-            .sum();
-        base_power + bonus
+    pub fn pos(&self, id: EntityId) -> (i32, i32) {
+        let position = self.positions[id];
+        (position.x, position.y)
     }
 
-    pub fn defense(&self, game: &Game) -> i32 {
-        let base_defense = self.fighter.map_or(0, |f| f.base_defense);
-        let bonus: i32 = self
-            .get_all_equipped(game)
-            .iter()
-            .map(|e| e.defense_bonus)
-            .sum();
-        base_defense + bonus
+    pub fn set_pos(&mut self, id: EntityId, x: i32, y: i32) {
+        self.positions.insert(id, Position { x, y });
     }
 
-    pub fn max_hp(&self, game: &Game) -> i32 {
-        let base_max_hp = self.fighter.map_or(0, |f| f.base_max_hp);
-        let bonus: i32 = self
-            .get_all_equipped(game)
+    // Everything on the given tile
+    pub fn at(&self, x: i32, y: i32) -> impl Iterator<Item = EntityId> + '_ {
+        self.positions
             .iter()
-            .map(|e| e.max_hp_bonus)
-            .sum();
-        base_max_hp + bonus
-    }
-
-    pub fn get_all_equipped(&self, game: &Game) -> Vec<Equipment> {
-        if self.name == "Player" {
-            game.inventory
-                .iter()
-                .filter(|item| item.equipment.map_or(false, |e| e.equipped))
-                .map(|item| item.equipment.unwrap())
-                .collect()
-        } else {
-            vec![]
-        }
-    }
-
-    pub fn equip(&mut self, messages: &mut Messages) {
-        if self.item.is_none() {
-            messages.add(
-                format!("Can't equip {:?} because it's not an Item.", self),
-                RED,
-            );
-            return;
-        }
-        if let Some(ref mut equipment) = self.equipment {
-            if !equipment.equipped {
-                equipment.equipped = true;
-                messages.add(
-                    format!("Equipped {} on {}", self.name, equipment.slot),
-                    LIGHT_GREEN,
-                );
-            }
-        } else {
-            messages.add(
-                format!("Can't equip {:?} because it's not an Equipment Item.", self),
-                RED,
-            );
-        }
-    }
-
-    pub fn dequip(&mut self, messages: &mut Messages) {
-        if self.item.is_none() {
-            messages.add(
-                format!("Can't dequip {:?} because it's not an Item.", self),
-                RED,
-            );
-            return;
-        }
-        if let Some(ref mut equipment) = self.equipment {
-            if !equipment.equipped {
-                equipment.equipped = false;
-                messages.add(
-                    format!("Dequipped {} on {}", self.name, equipment.slot),
-                    LIGHT_YELLOW,
-                );
-            }
-        } else {
-            messages.add(
-                format!(
-                    "Can't dequip {:?} because it's not an Equipment Item.",
-                    self
-                ),
-                RED,
-            );
-        }
+            .filter(move |&(_, position)| (position.x, position.y) == (x, y))
+            .map(|(id, _)| id)
     }
 
-    pub fn heal(&mut self, amount: i32, game: &Game) {
-        let max_hp = self.max_hp(game);
-        if let Some(ref mut fighter) = self.fighter {
-            fighter.hp += amount;
-            if fighter.hp > max_hp {
-                fighter.hp = max_hp;
-            }
+    pub fn move_by(&mut self, id: EntityId, dx: i32, dy: i32, map: &Map) {
+        let (x, y) = self.pos(id);
+        if !is_blocked(x + dx, y + dy, map, self) {
+            self.set_pos(id, x + dx, y + dy);
         }
     }
 
-    pub fn move_by(id: usize, dx: i32, dy: i32, map: &Map, objects: &mut [Object]) {
-        let (x, y) = objects[id].pos();
-        if !is_blocked(x + dx, y + dy, map, objects) {
-            objects[id].set_pos(x + dx, y + dy);
-        }
+    pub fn distance_to(&self, id: EntityId, other: EntityId) -> f32 {
+        let (x, y) = self.pos(other);
+        self.distance(id, x, y)
     }
 
-    pub fn draw(&self, con: &mut dyn Console) {
-        con.set_default_foreground(self.color);
-        con.put_char(self.x, self.y, self.char, BackgroundFlag::None);
-    }
-
-    pub fn pos(&self) -> (i32, i32) {
-        (self.x, self.y)
-    }
-
-    pub fn set_pos(&mut self, x: i32, y: i32) {
-        self.x = x;
-        self.y = y;
-    }
-
-    pub fn distance_to(&self, other: &Object) -> f32 {
-        let dx = other.x - self.x;
-        let dy = other.y - self.y;
-        ((dx.pow(2) + dy.pow(2)) as f32).sqrt()
-    }
-
-    pub fn take_damage(&mut self, damage: i32, game: &mut Game) -> Option<i32> {
-        // Apply damage if possible
-        if let Some(fighter) = self.fighter.as_mut() {
-            if damage > 0 {
-                if fighter.hp - damage < 0 {
-                    fighter.hp = 0;
-                } else {
-                    fighter.hp -= damage;
-                }
-            }
-        }
-        if let Some(fighter) = self.fighter {
-            if fighter.hp == 0 {
-                self.alive = false;
-                self.always_visible = true;
-                fighter.on_death.callback(self, game);
-                return Some(fighter.xp);
-            }
-        }
-        None
-    }
-
-    pub fn attack(&mut self, target: &mut Object, game: &mut Game) {
-        // Simple attack formula for damage
-        let damage = self.power(game) - target.defense(game);
-        if damage > 0 {
-            // Target takes damage
-            game.messages.add(
-                format!("{} attacks {} for {} hp", self.name, target.name, damage),
-                WHITE,
-            );
-            if let Some(xp) = target.take_damage(damage, game) {
-                // Yield xp to the player
-                self.fighter.as_mut().unwrap().xp += xp;
-            }
-        } else {
-            game.messages.add(
-                format!(
-                    "{} attacks {} but it has no effect!",
-                    self.name, target.name
-                ),
-                WHITE,
-            );
-        }
-    }
-
-    pub fn distance(&self, x: i32, y: i32) -> f32 {
-        (((x - self.x).pow(2) + (y - self.y).pow(2)) as f32).sqrt()
+    pub fn distance(&self, id: EntityId, x: i32, y: i32) -> f32 {
+        let (own_x, own_y) = self.pos(id);
+        (((x - own_x).pow(2) + (y - own_y).pow(2)) as f32).sqrt()
     }
 }
 
 pub fn target_tile(
     tcod: &mut Tcod,
     game: &mut Game,
-    objects: &[Object],
+    world: &World,
     max_range: Option<f32>,
 ) -> Option<(i32, i32)> {
     use tcod::input::KeyCode::Escape;
@@ -350,14 +221,14 @@ pub fn target_tile(
             Some(Event::Key(k)) => tcod.key = k,
             None => tcod.key = Default::default(),
         }
-        render_all(tcod, game, objects, false);
+        render_all(tcod, game, world, false);
 
         let (x, y) = (tcod.mouse.cx as i32, tcod.mouse.cy as i32);
 
         // Accept the target if the player clicked in FOV, and in case a range
         // is specified, if it's in that range
         let in_fov = (x < MAP_WIDTH) && (y < MAP_HEIGHT) && tcod.fov.is_in_fov(x, y);
-        let in_range = max_range.map_or(true, |range| objects[PLAYER].distance(x, y) <= range);
+        let in_range = max_range.is_none_or(|range| world.distance(PLAYER, x, y) <= range);
         if tcod.mouse.lbutton_pressed && in_fov && in_range {
             return Some((x, y));
         }
@@ -368,17 +239,32 @@ pub fn target_tile(
     }
 }
 
-pub fn is_blocked(x: i32, y: i32, map: &Map, objects: &[Object]) -> bool {
+pub fn is_blocked(x: i32, y: i32, map: &Map, world: &World) -> bool {
     if map[x as usize][y as usize].blocked {
         return true;
     }
 
-    objects
-        .iter()
-        .any(|object| object.blocks && object.pos() == (x, y))
+    world.at(x, y).any(|id| world.blockers.contains(id))
 }
 
-pub fn place_objects(room: Rect, map: &Map, objects: &mut Vec<Object>, level: u32) {
+// Find the closest free tile around (x, y), for putting creatures next to each other
+pub fn free_spot_near(x: i32, y: i32, map: &Map, world: &World) -> Option<(i32, i32)> {
+    for radius in 1..=2 {
+        for dx in -radius..=radius {
+            for dy in -radius..=radius {
+                let (spot_x, spot_y) = (x + dx, y + dy);
+                let in_map =
+                    spot_x >= 0 && spot_y >= 0 && spot_x < MAP_WIDTH && spot_y < MAP_HEIGHT;
+                if in_map && !is_blocked(spot_x, spot_y, map, world) {
+                    return Some((spot_x, spot_y));
+                }
+            }
+        }
+    }
+    None
+}
+
+pub fn place_objects(room: Rect, map: &Map, world: &mut World, level: u32, templates: &Templates) {
     // Max number of monsters per room
     let max_monsters = from_dungeon_level(
         &[
@@ -390,66 +276,30 @@ pub fn place_objects(room: Rect, map: &Map, objects: &mut Vec<Object>, level: u3
     );
     let num_monsters = rand::thread_rng().gen_range(0, max_monsters + 1);
 
-    let troll_chance = from_dungeon_level(
-        &[
-            Transition {
-                level: 3,
-                value: 15,
-            },
-            Transition {
-                level: 5,
-                value: 30,
-            },
-            Transition {
-                level: 7,
-                value: 60,
-            },
-        ],
-        level,
-    );
-
-    // Monster random choice table
-    let monster_chances = [("Orc", 80), ("Troll", troll_chance)];
-    let monster_choice = WeightedIndex::new(monster_chances.iter().map(|item| item.1)).unwrap();
+    // Monster random choice table, from the monster definitions
+    let monster_chances = templates
+        .monsters
+        .iter()
+        .map(|monster| monster.spawn_weight(level))
+        .collect::<Vec<_>>();
+    // There is no choice to make if nothing can spawn at this depth
+    let monster_choice = WeightedIndex::new(&monster_chances).ok();
 
     for _ in 0..num_monsters {
         let x = rand::thread_rng().gen_range(room.x1 + 1, room.x2);
         let y = rand::thread_rng().gen_range(room.y1 + 1, room.y2);
 
-        if !is_blocked(x, y, map, objects) {
-            let mut monster =
-                match monster_chances[monster_choice.sample(&mut rand::thread_rng())].0 {
-                    "Orc" => {
-                        let mut orc = Object::new(x, y, 'o', "Orc", DESATURATED_GREEN, true);
-                        orc.fighter = Some(Fighter {
-                            base_max_hp: 20,
-                            hp: 20,
-                            base_defense: 0,
-                            base_power: 4,
-                            xp: 35,
-                            on_death: DeathCallback::Monster,
-                        });
-                        orc.ai = Some(Ai::Basic);
-                        orc
-                    }
-                    "Troll" => {
-                        let mut troll = Object::new(x, y, 'T', "Troll", DARKER_GREEN, true);
-                        troll.fighter = Some(Fighter {
-                            base_max_hp: 30,
-                            hp: 30,
-                            base_defense: 2,
-                            base_power: 8,
-                            xp: 100,
-                            on_death: DeathCallback::Monster,
-                        });
-                        troll.ai = Some(Ai::Basic);
-                        troll
-                    }
-                    _ => unreachable!(),
+        if let Some(ref monster_choice) = monster_choice {
+            if !is_blocked(x, y, map, world) {
+                let template = &templates.monsters[monster_choice.sample(&mut rand::thread_rng())];
+                let monster = template.spawn(world, x, y, &templates.items);
+                let awareness = if rand::random::<f32>() < SLEEP_CHANCE {
+                    Awareness::Asleep
+                } else {
+                    Awareness::Unaware
                 };
-
-            monster.alive = true;
-            objects.push(monster);
+                world.awareness.insert(monster, awareness);
+            }
         }
     }
 
@@ -464,55 +314,13 @@ pub fn place_objects(room: Rect, map: &Map, objects: &mut Vec<Object>, level: u3
     // Choose random number of items
     let num_items = rand::thread_rng().gen_range(0, max_items + 1);
 
-    // Item random choice table
-    let item_chances = [
-        (Item::Heal, 35),
-        (
-            Item::Lightning,
-            from_dungeon_level(
-                &[Transition {
-                    level: 4,
-                    value: 25,
-                }],
-                level,
-            ),
-        ),
-        (
-            Item::Fireball,
-            from_dungeon_level(
-                &[Transition {
-                    level: 6,
-                    value: 25,
-                }],
-                level,
-            ),
-        ),
-        (
-            Item::Confuse,
-            from_dungeon_level(
-                &[Transition {
-                    level: 2,
-                    value: 10,
-                }],
-                level,
-            ),
-        ),
-        (
-            Item::Sword,
-            from_dungeon_level(&[Transition { level: 4, value: 5 }], level),
-        ),
-        (
-            Item::Shield,
-            from_dungeon_level(
-                &[Transition {
-                    level: 8,
-                    value: 15,
-                }],
-                level,
-            ),
-        ),
-    ];
-    let item_choice = WeightedIndex::new(item_chances.iter().map(|item| item.1)).unwrap();
+    // Item random choice table, from the item definitions
+    let item_chances = templates
+        .items
+        .iter()
+        .map(|item| item.spawn_weight(level))
+        .collect::<Vec<_>>();
+    let item_choice = WeightedIndex::new(&item_chances).ok();
 
     for _ in 0..num_items {
         // Choose random spot for this item
@@ -520,41 +328,13 @@ pub fn place_objects(room: Rect, map: &Map, objects: &mut Vec<Object>, level: u3
         let y = rand::thread_rng().gen_range(room.y1 + 1, room.y2);
 
         // Only place an item if the tile is not blocked
-        if !is_blocked(x, y, map, objects) {
-            let mut item = match item_chances[item_choice.sample(&mut rand::thread_rng())].0 {
-                Item::Heal => Potion::new(x, y, "Healing", Item::Heal),
-                Item::Lightning => Scroll::new(x, y, "Lightning Bolt", Item::Lightning),
-                Item::Fireball => Scroll::new(x, y, "Fireball", Item::Fireball),
-                Item::Confuse => Scroll::new(x, y, "Confusion", Item::Confuse),
-                Item::Sword => {
-                    // Create a sword
-                    let mut object = Object::new(x, y, '/', "Sword", SKY, false);
-                    object.item = Some(Item::Sword);
-                    object.equipment = Some(Equipment {
-                        equipped: false,
-                        slot: Slot::RightHand,
-                        max_hp_bonus: 0,
-                        defense_bonus: 0,
-                        power_bonus: 3,
-                    });
-                    object
-                }
-                Item::Shield => {
-                    // create a shield
-                    let mut object = Object::new(x, y, '[', "shield", DARKER_ORANGE, false);
-                    object.item = Some(Item::Shield);
-                    object.equipment = Some(Equipment {
-                        equipped: false,
-                        slot: Slot::LeftHand,
-                        max_hp_bonus: 0,
-                        defense_bonus: 1,
-                        power_bonus: 0,
-                    });
-                    object
-                }
-            };
-            item.always_visible = true;
-            objects.push(item);
+        if let Some(ref item_choice) = item_choice {
+            if !is_blocked(x, y, map, world) {
+                let template = &templates.items[item_choice.sample(&mut rand::thread_rng())];
+                let item = template.spawn(world, x, y);
+                roll_affixes(item, level, &templates.affixes, world);
+                roll_gold(item, level, world);
+            }
         }
     }
 }
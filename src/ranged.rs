@@ -0,0 +1,148 @@
+use crate::damage::DamageType;
+use crate::entity::{EntityId, World, PLAYER};
+use crate::equipment::{get_equipped_in_slot, Slot};
+use crate::game::{Game, IMPACT_NOISE};
+use crate::item::{place_item, take_one};
+use crate::map::Map;
+use crate::object::target_tile;
+use crate::Tcod;
+use serde::{Deserialize, Serialize};
+use tcod::colors::*;
+use tcod::line::Line;
+
+// Chance that a projectile is destroyed when it hits something
+const AMMO_BREAK_CHANCE: f32 = 0.25;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AmmoKind {
+    Arrow,
+    Bolt,
+    Stone,
+}
+
+impl std::fmt::Display for AmmoKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            AmmoKind::Arrow => write!(f, "arrow"),
+            AmmoKind::Bolt => write!(f, "bolt"),
+            AmmoKind::Stone => write!(f, "stone"),
+        }
+    }
+}
+
+// A bow, crossbow or sling: what it shoots and how hard
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RangedWeapon {
+    pub ammo: AmmoKind,
+    pub range: i32,
+    pub power: i32,
+}
+
+// Projectiles for a kind of ranged weapon. How many there are is in the
+// item's stack
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Ammo {
+    pub kind: AmmoKind,
+}
+
+// Follow the line from `from` to `to`, stopping at the first wall or creature.
+// Returns the creature that was hit, if any, and the tile the projectile ended on.
+pub fn trace_projectile(
+    from: (i32, i32),
+    to: (i32, i32),
+    map: &Map,
+    world: &World,
+) -> (Option<EntityId>, (i32, i32)) {
+    let mut landing = from;
+    for (x, y) in Line::new(from, to) {
+        if map[x as usize][y as usize].blocked {
+            // Hit a wall, fall down in front of it
+            return (None, landing);
+        }
+        landing = (x, y);
+        let target = world.at(x, y).find(|&id| world.fighters.contains(id));
+        if target.is_some() {
+            return (target, landing);
+        }
+    }
+    (None, landing)
+}
+
+pub fn fire_weapon(tcod: &mut Tcod, game: &mut Game, world: &mut World) -> bool {
+    let (weapon, enchantment) = match get_equipped_in_slot(Slot::Ranged, PLAYER, world)
+        .and_then(|id| Some((*world.ranged.get(id)?, world.equipment[id].enchantment)))
+    {
+        Some(weapon) => weapon,
+        None => {
+            game.messages
+                .add("You have no ranged weapon equipped.", RED);
+            return false;
+        }
+    };
+    let ammo_id = match world.inventories[PLAYER].iter().cloned().find(|&id| {
+        world
+            .ammo
+            .get(id)
+            .is_some_and(|ammo| ammo.kind == weapon.ammo)
+    }) {
+        Some(ammo_id) => ammo_id,
+        None => {
+            game.messages
+                .add(format!("You have no {}s to fire.", weapon.ammo), RED);
+            return false;
+        }
+    };
+
+    game.messages.add(
+        "Left-click a target tile to fire at, or right-click to cancel",
+        LIGHT_CYAN,
+    );
+    let target = match target_tile(tcod, game, world, Some(weapon.range as f32)) {
+        Some(tile_pos) => tile_pos,
+        None => return false,
+    };
+
+    // Take one projectile out of the stack
+    let projectile = take_one(PLAYER, ammo_id, game, world);
+
+    let (hit, (x, y)) = trace_projectile(world.pos(PLAYER), target, &game.map, world);
+    game.make_noise(x, y, IMPACT_NOISE);
+    match hit {
+        Some(target_id) => {
+            let damage = weapon.power + enchantment - world.defense(target_id);
+            let dealt = world.resisted_damage(target_id, damage, DamageType::Physical);
+            if dealt > 0 {
+                game.messages.add(
+                    format!(
+                        "Your {} hits the {} for {} hit points.",
+                        weapon.ammo, world.names[target_id], dealt
+                    ),
+                    WHITE,
+                );
+                if let Some(xp) = world.take_damage(target_id, damage, DamageType::Physical, game) {
+                    world.fighters[PLAYER].xp += xp;
+                }
+            } else {
+                game.messages.add(
+                    format!(
+                        "Your {} bounces off the {}.",
+                        weapon.ammo, world.names[target_id]
+                    ),
+                    WHITE,
+                );
+            }
+            if rand::random::<f32>() < AMMO_BREAK_CHANCE {
+                game.messages
+                    .add(format!("The {} breaks.", weapon.ammo), LIGHT_GREY);
+                world.despawn(projectile);
+                return true;
+            }
+        }
+        None => {
+            game.messages
+                .add(format!("Your {} hits nothing.", weapon.ammo), LIGHT_GREY);
+        }
+    }
+    place_item(projectile, x, y, world);
+    true
+}
@@ -0,0 +1,346 @@
+use crate::affix::{roll_affixes, Rarity};
+use crate::ai::{Awareness, HUNT_TURNS};
+use crate::container::despawn_with_contents;
+use crate::entity::{EntityId, World, PLAYER};
+use crate::game::{Game, Templates};
+use crate::identify::Knowledge;
+use crate::item::{
+    add_to_inventory, count, describe, fits_in_inventory, place_item, take_from_inventory, Item,
+};
+use crate::map::{inventory_menu, menu, Map, Rect};
+use crate::object::is_blocked;
+use crate::Tcod;
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use tcod::colors::*;
+
+pub const GOLD_NAME: &str = "gold pieces";
+const SHOPKEEPER: &str = "Shopkeeper";
+
+// Chance for a level to have a shop, and how much is for sale in one
+const SHOP_CHANCE: f32 = 0.3;
+const MIN_STOCK: i32 = 4;
+const MAX_STOCK: i32 = 8;
+// Shopkeepers stop buying once they have this many things out for sale
+const MAX_WARES: usize = 26;
+// Shopkeepers pay this fraction of what they would sell an item for
+const SELL_FRACTION: f32 = 0.5;
+// Gold piles on the floor hold up to this much per dungeon level
+const GOLD_PER_LEVEL: i32 = 15;
+// Added to an item's price for each point of enchantment
+const ENCHANTMENT_PRICE: i32 = 20;
+const TRADE_WIDTH: i32 = 50;
+
+// The gold a creature carries
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Purse {
+    pub gold: i32,
+}
+
+// Keeps a shop, and stays at its post unless robbed
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Shopkeeper {
+    pub post: (i32, i32),
+    pub angry: bool,
+}
+
+// An item for sale, and whose shop it's in
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Merchandise {
+    pub keeper: EntityId,
+}
+
+// What an item is worth, counting every item in its stack. Nothing is worth
+// anything to a shopkeeper without a price
+pub fn item_value(id: EntityId, world: &World) -> i32 {
+    let price = match world.prices.get(id) {
+        Some(&price) => price,
+        None => return 0,
+    };
+    let price = match world.equipment.get(id) {
+        Some(equipment) => {
+            let multiplier = match equipment.rarity {
+                Rarity::Common => 1,
+                Rarity::Magic => 2,
+                Rarity::Rare => 4,
+            };
+            price * multiplier + equipment.enchantment * ENCHANTMENT_PRICE
+        }
+        None => price,
+    };
+    (price * count(id, world)).max(1)
+}
+
+// Make a gold pile found on the given level worth something
+pub fn roll_gold(item_id: EntityId, level: u32, world: &mut World) {
+    if world.items.get(item_id) == Some(&Item::Gold) {
+        let amount = rand::thread_rng().gen_range(1, GOLD_PER_LEVEL * level as i32 + 1);
+        world.stacks[item_id].count = amount;
+    }
+}
+
+// Put a pile of gold on the map
+pub fn drop_gold(amount: i32, x: i32, y: i32, templates: &Templates, world: &mut World) {
+    if let Some(template) = templates.item(GOLD_NAME) {
+        let pile = template.spawn(world, x, y);
+        world.stacks[pile].count = amount;
+    }
+}
+
+// Maybe turn one of the rooms between the first and the last into a shop
+pub fn place_shop(rooms: &[Rect], map: &Map, world: &mut World, level: u32, templates: &Templates) {
+    if rooms.len() < 3 || rand::random::<f32>() >= SHOP_CHANCE {
+        return;
+    }
+    let template = match templates.monsters.iter().find(|t| t.name == SHOPKEEPER) {
+        Some(template) => template,
+        None => return,
+    };
+    let room = rooms[rand::thread_rng().gen_range(1, rooms.len() - 1)];
+    let inside = |x: i32, y: i32| x > room.x1 && x < room.x2 && y > room.y1 && y < room.y2;
+
+    // Clear out whatever was put there before the shop opened
+    let squatters = world
+        .positions
+        .iter()
+        .filter(|&(id, p)| id != PLAYER && inside(p.x, p.y))
+        .map(|(id, _)| id)
+        .collect::<Vec<_>>();
+    for id in squatters {
+        despawn_with_contents(id, world);
+    }
+
+    let post = (room.x1 + 1, room.y1 + 1);
+    let keeper = template.spawn(world, post.0, post.1, &templates.items);
+    world.awareness.insert(keeper, Awareness::Unaware);
+    world
+        .shopkeepers
+        .insert(keeper, Shopkeeper { post, angry: false });
+
+    // Stock the shelves with anything that has a price
+    let chances = templates
+        .items
+        .iter()
+        .map(|item| {
+            if item.price > 0 {
+                item.spawn_weight(level)
+            } else {
+                0
+            }
+        })
+        .collect::<Vec<_>>();
+    let choice = match WeightedIndex::new(&chances) {
+        Ok(choice) => choice,
+        Err(_) => return,
+    };
+    let stock = rand::thread_rng().gen_range(MIN_STOCK, MAX_STOCK + 1);
+    for _ in 0..stock {
+        let x = rand::thread_rng().gen_range(room.x1 + 1, room.x2);
+        let y = rand::thread_rng().gen_range(room.y1 + 1, room.y2);
+        if is_blocked(x, y, map, world) || world.at(x, y).next().is_some() {
+            continue;
+        }
+        let template = &templates.items[choice.sample(&mut rand::thread_rng())];
+        let item = template.spawn(world, x, y);
+        roll_affixes(item, level, &templates.affixes, world);
+        world.wares.insert(item, Merchandise { keeper });
+    }
+}
+
+// Taking something off a shop's floor without paying makes its keeper turn on the thief
+pub fn check_theft(item_id: EntityId, game: &mut Game, world: &mut World) {
+    let keeper = match world.wares.remove(item_id) {
+        Some(merchandise) => merchandise.keeper,
+        None => return,
+    };
+    let shopkeeper = match world.shopkeepers.get_mut(keeper) {
+        Some(shopkeeper) if world.fighters.contains(keeper) => shopkeeper,
+        // Nobody left to mind the shop
+        _ => return,
+    };
+    if !shopkeeper.angry {
+        shopkeeper.angry = true;
+        game.messages.add(
+            format!("\"Thief!\" shouts the {}.", world.names[keeper]),
+            RED,
+        );
+    }
+    let (x, y) = world.pos(PLAYER);
+    world.awareness.insert(
+        keeper,
+        Awareness::Hunting {
+            x,
+            y,
+            turns_left: HUNT_TURNS,
+        },
+    );
+}
+
+pub fn is_angry(id: EntityId, world: &World) -> bool {
+    world.shopkeepers.get(id).is_some_and(|s| s.angry)
+}
+
+// Buy from or sell to a shopkeeper the player bumped into
+pub fn trade(keeper: EntityId, tcod: &mut Tcod, game: &mut Game, world: &mut World) {
+    let gold = world.purses.get(PLAYER).map_or(0, |p| p.gold);
+    let header = format!(
+        "\"Welcome, traveller!\" says the {}. You have {} gold.\n",
+        world.names[keeper], gold
+    );
+    match menu(&header, &["Buy", "Sell"], TRADE_WIDTH, &mut tcod.root) {
+        Some(0) => buy(keeper, tcod, game, world),
+        Some(1) => sell(keeper, tcod, game, world),
+        _ => {}
+    }
+}
+
+// What a shopkeeper has out for sale
+fn stock(keeper: EntityId, world: &World) -> Vec<EntityId> {
+    world
+        .wares
+        .iter()
+        .filter(|&(id, m)| m.keeper == keeper && world.positions.contains(id))
+        .map(|(id, _)| id)
+        .collect()
+}
+
+fn shelves_full(keeper: EntityId, world: &World) -> bool {
+    stock(keeper, world).len() >= MAX_WARES
+}
+
+fn buy(keeper: EntityId, tcod: &mut Tcod, game: &mut Game, world: &mut World) {
+    let stock = stock(keeper, world);
+    if stock.is_empty() {
+        game.messages.add("\"I'm afraid I've sold out.\"", WHITE);
+        return;
+    }
+    let options = stock
+        .iter()
+        .map(|&id| price_tag(id, &game.knowledge, world, item_value(id, world)))
+        .collect::<Vec<_>>();
+    let item_id = match menu(
+        "Press the key next to an item to buy it, or any other to cancel.\n",
+        &options,
+        TRADE_WIDTH,
+        &mut tcod.root,
+    ) {
+        Some(index) => stock[index],
+        None => return,
+    };
+
+    let price = item_value(item_id, world);
+    let gold = world.purses.get(PLAYER).map_or(0, |p| p.gold);
+    if gold < price {
+        game.messages
+            .add("\"Come back when you can afford it.\"", RED);
+        return;
+    }
+    if !fits_in_inventory(item_id, game, world) {
+        return;
+    }
+    world.purses.get_or_insert_default(PLAYER).gold -= price;
+    world.wares.remove(item_id);
+    game.messages.add(
+        format!(
+            "You buy the {} for {} gold.",
+            describe(item_id, &game.knowledge, world),
+            price
+        ),
+        GREEN,
+    );
+    add_to_inventory(PLAYER, item_id, world);
+}
+
+fn sell(keeper: EntityId, tcod: &mut Tcod, game: &mut Game, world: &mut World) {
+    let item_id = match inventory_menu(
+        &world.inventories[PLAYER],
+        &game.knowledge,
+        world,
+        "Press the key next to an item to sell it, or any other to cancel.\n",
+        &mut tcod.root,
+    ) {
+        Some(item_id) => item_id,
+        None => return,
+    };
+    let offer = (item_value(item_id, world) as f32 * SELL_FRACTION) as i32;
+    if world.prices.get(item_id).is_none() || offer <= 0 {
+        game.messages.add("\"I've no use for that.\"", WHITE);
+        return;
+    }
+    if world
+        .inventories
+        .get(item_id)
+        .is_some_and(|c| !c.is_empty())
+    {
+        game.messages
+            .add("\"Empty it out first, if you please.\"", WHITE);
+        return;
+    }
+    if shelves_full(keeper, world) {
+        game.messages.add("\"My shelves are full.\"", WHITE);
+        return;
+    }
+    if !world.can_remove(item_id, &mut game.messages) {
+        return;
+    }
+    game.messages.add(
+        format!(
+            "You sell the {} for {} gold.",
+            describe(item_id, &game.knowledge, world),
+            offer
+        ),
+        GREEN,
+    );
+    take_from_inventory(PLAYER, item_id, game, world);
+    world.purses.get_or_insert_default(PLAYER).gold += offer;
+    // The keeper puts it out for sale by their post, on any pile of the same
+    world.wares.insert(item_id, Merchandise { keeper });
+    let (x, y) = world.shopkeepers[keeper].post;
+    place_item(item_id, x, y, world);
+}
+
+fn price_tag(id: EntityId, knowledge: &Knowledge, world: &World, price: i32) -> String {
+    format!("{} ({} gold)", describe(id, knowledge, world), price)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::Stack;
+
+    fn spawn_ware(world: &mut World, keeper: EntityId, name: &str) -> EntityId {
+        let id = world.spawn();
+        world.names.insert(id, name.into());
+        world.items.insert(id, Item::Heal { amount: 10 });
+        world.stacks.insert(id, Stack { count: 1 });
+        world.wares.insert(id, Merchandise { keeper });
+        world.set_pos(id, 1, 1);
+        id
+    }
+
+    #[test]
+    fn shelves_fill_up_at_the_most_wares() {
+        let mut world = World::default();
+        let keeper = world.spawn();
+        for i in 0..MAX_WARES - 1 {
+            spawn_ware(&mut world, keeper, &format!("Potion {}", i));
+        }
+        assert!(!shelves_full(keeper, &world));
+        spawn_ware(&mut world, keeper, "Scroll");
+        assert!(shelves_full(keeper, &world));
+    }
+
+    #[test]
+    fn only_the_keepers_own_wares_on_the_floor_are_stock() {
+        let mut world = World::default();
+        let keeper = world.spawn();
+        let rival = world.spawn();
+        let ware = spawn_ware(&mut world, keeper, "Potion");
+        spawn_ware(&mut world, rival, "Potion");
+        let carried = spawn_ware(&mut world, keeper, "Scroll");
+        world.positions.remove(carried);
+        assert_eq!(stock(keeper, &world), vec![ware]);
+        assert!(!shelves_full(keeper, &world));
+    }
+}
@@ -0,0 +1,82 @@
+use crate::damage::DamageType;
+use crate::entity::{EntityId, World};
+use crate::game::Game;
+use serde::{Deserialize, Serialize};
+
+// Damage taken each turn while poisoned
+const POISON_DAMAGE: i32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum StatusKind {
+    Confused,
+    Enraged,
+    Afraid,
+    Hasted,
+    Invisible,
+    Telepathic,
+    Poisoned,
+}
+
+impl std::fmt::Display for StatusKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            StatusKind::Confused => write!(f, "confused"),
+            StatusKind::Enraged => write!(f, "enraged"),
+            StatusKind::Afraid => write!(f, "afraid"),
+            StatusKind::Hasted => write!(f, "hasted"),
+            StatusKind::Invisible => write!(f, "invisible"),
+            StatusKind::Telepathic => write!(f, "telepathic"),
+            StatusKind::Poisoned => write!(f, "poisoned"),
+        }
+    }
+}
+
+// A temporary effect on a creature that wears off after a number of turns
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Status {
+    pub kind: StatusKind,
+    pub turns: i32,
+}
+
+impl World {
+    pub fn has_status(&self, id: EntityId, kind: StatusKind) -> bool {
+        self.statuses
+            .get(id)
+            .is_some_and(|statuses| statuses.iter().any(|s| s.kind == kind))
+    }
+
+    pub fn add_status(&mut self, id: EntityId, kind: StatusKind, turns: i32) {
+        let statuses = self.statuses.get_or_insert_default(id);
+        // Reapplying an effect only refreshes its duration
+        match statuses.iter_mut().find(|s| s.kind == kind) {
+            Some(status) => status.turns = status.turns.max(turns),
+            None => statuses.push(Status { kind, turns }),
+        }
+    }
+
+    // Hurt a poisoned creature, returning the xp it yields if that kills it
+    pub fn suffer_poison(&mut self, id: EntityId, game: &mut Game) -> Option<i32> {
+        if !self.has_status(id, StatusKind::Poisoned) {
+            return None;
+        }
+        self.take_damage(id, POISON_DAMAGE, DamageType::Poison, game)
+    }
+
+    // Count down temporary effects, returning the ones that wore off
+    pub fn tick_statuses(&mut self, id: EntityId) -> Vec<StatusKind> {
+        let statuses = match self.statuses.get_mut(id) {
+            Some(statuses) => statuses,
+            None => return vec![],
+        };
+        for status in statuses.iter_mut() {
+            status.turns -= 1;
+        }
+        let expired = statuses
+            .iter()
+            .filter(|s| s.turns < 0)
+            .map(|s| s.kind)
+            .collect();
+        statuses.retain(|s| s.turns >= 0);
+        expired
+    }
+}